@@ -0,0 +1,864 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::vec::Vec;
+
+use crate::config::{DuelConfig, JobConfig, XpConfig};
+use crate::data::GameData;
+use crate::effects::{ActiveEffect, Effect, EffectCatalogue, Restriction};
+use crate::error::{Error, Result};
+use crate::modifiers::{self, Breakdown, Modifier};
+use crate::names::{fold, suggestions};
+use crate::ranks::Promotion;
+use crate::stats::{IvStats, RaceTable};
+use crate::storage::AdventurerRepository;
+use crate::weapons::{EquippedWeapon, Weapon, WeaponCatalogue};
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Adventurers {
+    pub adventurer: Vec<Adventurer>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Adventurer {
+    pub id: u32,
+    pub name: String,
+    pub race: Race,
+    pub rank: Rank,
+    pub level: u8,
+    /// Earned since the last level up, kept when the rank caps the level.
+    #[serde(default)]
+    pub xp: u32,
+    #[serde(default)]
+    pub quests: u32,
+    pub iv: IvStats,
+    pub jobs: Jobs,
+    /// Job XP earned since the last level of each job, by key.
+    #[serde(default)]
+    pub job_xp: BTreeMap<String, u32>,
+    pub energy: Energy,
+    pub health: Health,
+    #[serde(default)]
+    pub weapon: Option<EquippedWeapon>,
+    #[serde(default)]
+    pub effects: Vec<ActiveEffect>,
+    /// Oldest first.
+    #[serde(default)]
+    pub promotions: Vec<Promotion>,
+}
+
+pub const LEVEL_MAX: u8 = 100;
+pub const IV_MAX: u8 = 31;
+pub const JOB_LEVEL_MAX: u8 = 10;
+
+fn parse_in_range(field: &str, value: &str, min: u8, max: u8) -> Result<u8, String> {
+    match value.parse::<u8>() {
+        Ok(x) if (min..=max).contains(&x) => Ok(x),
+        _ => Err(format!(
+            "{} doit être un nombre entre {} et {} (reçu: {})",
+            field, min, max, value
+        )),
+    }
+}
+
+// Energy values are written "actuelle/max", e.g. "8/10".
+fn parse_energy(field: &str, value: &str) -> Result<(u8, u8), String> {
+    let usage = || {
+        format!(
+            "{} attend une valeur de la forme actuelle/max (reçu: {})",
+            field, value
+        )
+    };
+    let (actual, max) = value.split_once('/').ok_or_else(usage)?;
+    let actual: u8 = actual.parse().map_err(|_| usage())?;
+    let max: u8 = max.parse().map_err(|_| usage())?;
+    if actual > max {
+        return Err(format!(
+            "{}: {} dépasse le maximum de {}",
+            field, actual, max
+        ));
+    }
+
+    Ok((actual, max))
+}
+
+impl Adventurer {
+    pub fn new(name: &str, race: &str, rank: &str, level: Option<&str>) -> Result<Self> {
+        let mut adventurer = Adventurer {
+            id: 0,
+            name: String::new(),
+            race: race
+                .parse()
+                .map_err(|_| Error::UnknownRace(race.to_string()))?,
+            rank: rank.parse().map_err(Error::Validation)?,
+            level: 1,
+            xp: 0,
+            quests: 0,
+            iv: IvStats {
+                force: 0,
+                resistance: 0,
+                vitesse: 0,
+                resistance_magique: 0,
+                force_magique: 0,
+            },
+            jobs: Jobs::default(),
+            job_xp: BTreeMap::new(),
+            energy: Energy {
+                physical: Physical {
+                    actual_energy: 0,
+                    energy: 0,
+                },
+                magical: Vec::new(),
+            },
+            health: Health {
+                state: HealthState::Aucune,
+                description: "Aucune".to_string(),
+            },
+            weapon: None,
+            effects: Vec::new(),
+            promotions: Vec::new(),
+        };
+        adventurer.set_field("nom", name)?;
+        if let Some(level) = level {
+            adventurer.level =
+                parse_in_range("niveau", level, 1, LEVEL_MAX).map_err(Error::Validation)?;
+        }
+
+        Ok(adventurer)
+    }
+
+    /// Validates `value` for the French field name used by `?aventurier
+    /// modifier` and applies it. Jobs are addressed by their key, magic
+    /// pools by `magie.<nom>`.
+    pub fn set_field(&mut self, field: &str, value: &str) -> Result<()> {
+        self.apply_field(field, value).map_err(Error::Validation)
+    }
+
+    fn apply_field(&mut self, field: &str, value: &str) -> Result<(), String> {
+        match field {
+            "nom" => {
+                if value.is_empty() || value.chars().any(char::is_whitespace) {
+                    return Err("le nom doit être un seul mot non vide".to_string());
+                }
+                self.name = value.to_string();
+            }
+            "race" => self.race = value.parse()?,
+            // Promotions are recorded and levels follow the XP rules.
+            "rang" => {
+                return Err(
+                    "le rang ne se modifie pas: il change par une promotion (rang promouvoir)"
+                        .to_string(),
+                )
+            }
+            "niveau" => {
+                return Err(
+                    "le niveau ne se modifie pas: il monte avec l'XP (xp donner)".to_string(),
+                )
+            }
+            "quetes" => {
+                self.quests = value
+                    .parse()
+                    .map_err(|_| format!("quetes doit être un nombre positif (reçu: {})", value))?
+            }
+            "for" => self.iv.force = parse_in_range(field, value, 0, IV_MAX)?,
+            "res" => self.iv.resistance = parse_in_range(field, value, 0, IV_MAX)?,
+            "vit" => self.iv.vitesse = parse_in_range(field, value, 0, IV_MAX)?,
+            "fmag" => self.iv.force_magique = parse_in_range(field, value, 0, IV_MAX)?,
+            "rmag" => self.iv.resistance_magique = parse_in_range(field, value, 0, IV_MAX)?,
+            "energie" => {
+                let (actual, max) = parse_energy(field, value)?;
+                self.energy.physical.actual_energy = actual;
+                self.energy.physical.energy = max;
+            }
+            "sante" => self.health.state = value.parse()?,
+            "blessures" => self.health.description = value.to_string(),
+            _ => {
+                if let Some(school) = field.strip_prefix("magie.") {
+                    return self.set_magic(school, value);
+                }
+                let level = parse_in_range(field, value, 0, JOB_LEVEL_MAX);
+                match self.jobs.level_mut(field) {
+                    Some(slot) => *slot = level?,
+                    None => return Err(format!("champ inconnu: {}", field)),
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    // "aucune" removes the pool, anything else creates or updates it.
+    fn set_magic(&mut self, school: &str, value: &str) -> Result<(), String> {
+        if school.is_empty() {
+            return Err("le nom de l'école de magie est vide".to_string());
+        }
+        if value.eq_ignore_ascii_case("aucune") {
+            let before = self.energy.magical.len();
+            self.energy.magical.retain(|m| m.name != school);
+            if self.energy.magical.len() == before {
+                return Err(format!("{} n'a pas d'énergie {}", self.name, school));
+            }
+            return Ok(());
+        }
+        let (actual, max) = parse_energy(&format!("magie.{}", school), value)?;
+        match self.energy.magical.iter_mut().find(|m| m.name == school) {
+            Some(magic) => {
+                magic.actual_energy = actual;
+                magic.energy = max;
+            }
+            None => self.energy.magical.push(Magic {
+                name: school.to_string(),
+                actual_energy: actual,
+                energy: max,
+            }),
+        }
+
+        Ok(())
+    }
+}
+
+pub const EDITABLE_FIELDS: &str =
+    "nom, race, quetes, for, res, vit, fmag, rmag, energie, sante, blessures, magie.<école>, <métier>";
+
+impl Adventurer {
+    /// The catalogue entry of the weapon they carry, unless it is broken.
+    /// A weapon removed from the catalogue since is left out with a note.
+    pub fn equipped_weapon<'a>(
+        &self,
+        weapons: &'a WeaponCatalogue,
+        notes: &mut Vec<String>,
+    ) -> Option<&'a Weapon> {
+        let equipped = self
+            .weapon
+            .as_ref()
+            .filter(|equipped| !equipped.is_broken())?;
+        let weapon = weapons.find(&equipped.name);
+        if weapon.is_none() {
+            notes.push(format!(
+                "{} n'est plus dans le catalogue des armes, {} se bat comme sans arme.",
+                equipped.name, self.name
+            ));
+        }
+        weapon
+    }
+
+    /// The modifiers of the weapon they carry, unless it is broken or
+    /// unknown, of their effects and of their injuries.
+    pub fn modifiers(
+        &self,
+        data: &GameData,
+        settings: &DuelConfig,
+        notes: &mut Vec<String>,
+    ) -> Vec<Modifier> {
+        let weapon = self.equipped_weapon(&data.weapons, notes);
+        modifiers::of(self, weapon, &data.effects, settings)
+    }
+
+    /// Adds `amount` XP and levels up as long as there is enough, up to
+    /// the cap of their rank. Returns the number of levels gained.
+    pub fn gain_xp(&mut self, amount: u32, settings: &XpConfig) -> u8 {
+        self.xp = self.xp.saturating_add(amount);
+        self.level_up(settings)
+    }
+
+    /// Spends the XP kept for levels they can now reach, after a promotion
+    /// for instance.
+    pub fn level_up(&mut self, settings: &XpConfig) -> u8 {
+        let before = self.level;
+        let cap = settings.level_cap(self.rank).min(LEVEL_MAX);
+        while self.level < cap && self.xp >= settings.to_next(self.level) {
+            self.xp -= settings.to_next(self.level);
+            self.level += 1;
+        }
+        self.level - before
+    }
+
+    /// Adds `amount` XP to `job` and levels it up as long as there is
+    /// enough, up to `JOB_LEVEL_MAX`. Returns the number of levels gained.
+    pub fn gain_job_xp(&mut self, job: &str, amount: u32, settings: &JobConfig) -> u8 {
+        let level = match self.jobs.level_mut(job) {
+            Some(level) => level,
+            None => return 0,
+        };
+        let xp = self.job_xp.entry(job.to_string()).or_default();
+        *xp = xp.saturating_add(amount);
+        let before = *level;
+        while *level < JOB_LEVEL_MAX && *xp >= settings.to_next(*level) {
+            *xp -= settings.to_next(*level);
+            *level += 1;
+        }
+        *level - before
+    }
+
+    /// The first of their effects that forbids `restriction`. Effects
+    /// removed from the catalogue no longer do anything.
+    pub fn restricted<'a>(
+        &self,
+        catalogue: &'a EffectCatalogue,
+        restriction: Restriction,
+    ) -> Option<&'a Effect> {
+        self.effects
+            .iter()
+            .filter_map(|active| catalogue.find(&active.name))
+            .find(|effect| effect.restrictions.contains(&restriction))
+    }
+
+    /// Applies the drain of every tick of `tick` seconds elapsed since the
+    /// last one, up to `now` or the end of the effect, then removes the
+    /// effects that are over. Returns what a player would notice: the energy
+    /// drained or regained, and the effects that ended.
+    pub fn tick_effects(
+        &mut self,
+        catalogue: &EffectCatalogue,
+        now: i64,
+        tick: i64,
+    ) -> Vec<String> {
+        let mut notes = Vec::new();
+        let physical = &mut self.energy.physical;
+        for active in self.effects.iter_mut() {
+            let drain = catalogue
+                .find(&active.name)
+                .map_or(0, |effect| effect.drain);
+            let ticks = (now.min(active.expires_at) - active.last_tick) / tick;
+            if drain == 0 || ticks <= 0 {
+                continue;
+            }
+            active.last_tick += ticks * tick;
+            let before = physical.actual_energy;
+            let energy = before as i64 - drain as i64 * ticks;
+            physical.actual_energy = energy.clamp(0, physical.energy as i64) as u8;
+            if physical.actual_energy != before {
+                notes.push(format!(
+                    "{} ({}): énergie physique {} → {}",
+                    self.name, active.name, before, physical.actual_energy
+                ));
+            }
+        }
+
+        let (over, active): (Vec<ActiveEffect>, Vec<ActiveEffect>) = self
+            .effects
+            .drain(..)
+            .partition(|active| active.expires_at <= now);
+        self.effects = active;
+        for effect in over {
+            notes.push(format!(
+                "{} n'est plus sous l'effet de {}",
+                self.name, effect.name
+            ));
+        }
+        notes
+    }
+
+    /// The sheet shown by `?stats`. Stats are computed from the race table,
+    /// which is why this is not a `Display` impl; those changed by
+    /// `modifiers` are detailed under it.
+    pub fn sheet(&self, races: &RaceTable, modifiers: &[Modifier]) -> Result<String> {
+        let breakdown = |race| -> Result<Breakdown> {
+            Ok(Breakdown::new(
+                self.iv,
+                self.level,
+                races.get(race)?,
+                modifiers,
+            ))
+        };
+        let (mut res, details) = match self.race {
+            Race::Jiaodan => {
+                let human = breakdown(Race::JiaodanHumain)?;
+                let dragon = breakdown(Race::JiaodanDragon)?;
+                let details: Vec<String> = human
+                    .lines()
+                    .into_iter()
+                    .map(|line| format!("Humain: {}", line))
+                    .chain(
+                        dragon
+                            .lines()
+                            .into_iter()
+                            .map(|line| format!("Dragon: {}", line)),
+                    )
+                    .collect();
+                (format!(
+                    "```\nAventurier: {}\nRace: {}\nRank: {} ({} quêtes)\nLevel: {} ({} XP)\nStats Humain: {}\nStats Dragon: {}\nBlessures: {} ({})\nMétiers: {}\nEnergie physique: {}\n",
+                    self.name, self.race, self.rank, self.quests, self.level, self.xp, human.total, dragon.total, self.health.description, self.health.state, self.jobs, self.energy.physical), details)
+            }
+            _ => {
+                let stats = breakdown(self.race)?;
+                (format!(
+                "```\nAventurier: {}\nRace: {}\nRank: {} ({} quêtes)\nLevel: {} ({} XP)\nStats: {}\nBlessures: {} ({})\nMétiers: {}\nEnergie physique: {}\n",
+                self.name, self.race, self.rank, self.quests, self.level, self.xp, stats.total, self.health.description, self.health.state, self.jobs, self.energy.physical), stats.lines())
+            }
+        };
+        if !self.energy.magical.is_empty() {
+            res.push_str("Energie magique:");
+            for i in &self.energy.magical {
+                res.push_str(&i.to_string());
+            }
+            res.push('\n');
+        }
+        if let Some(weapon) = &self.weapon {
+            res.push_str(&format!("Arme: {}\n", weapon));
+        }
+        if !self.effects.is_empty() {
+            let names: Vec<&str> = self.effects.iter().map(|e| e.name.as_str()).collect();
+            res.push_str(&format!("Effets: {}\n", names.join(", ")));
+        }
+        if !details.is_empty() {
+            res.push_str(&format!("Détail:\n{}\n", details.join("\n")));
+        }
+        res.push_str("```");
+
+        Ok(res)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Jobs {
+    pub alchimiste_pharmacien: u8,
+    pub alchimiste_artificer: u8,
+    pub chevalier: u8,
+    pub archer: u8,
+    pub combattant: u8,
+    pub escarpe: u8,
+    pub medecin: u8,
+    pub dresseur: u8,
+    pub chasseur: u8,
+    pub agriculteur: u8,
+    pub couturier: u8,
+    pub historien: u8,
+    pub forgeron: u8,
+    pub cartographe: u8,
+    pub cuisinier: u8,
+    pub erudit: u8,
+    pub musicien: u8,
+    pub machiniste: u8,
+    pub ingenieur: u8,
+}
+
+/// Names shown to players, in the order of `Jobs::levels`.
+const JOB_LABELS: [&str; 19] = [
+    "Alchimiste pharmacien",
+    "Alchimiste artificer",
+    "Chevalier",
+    "Archer",
+    "Combattant",
+    "Escarpe",
+    "Medecin",
+    "Dresseur",
+    "Chasseur",
+    "Agriculteur",
+    "Couturier",
+    "Historien",
+    "Forgeron",
+    "Cartographe",
+    "Cuisinier",
+    "Erudit",
+    "Musicien",
+    "Machiniste mécanicien",
+    "Machiniste ingénieur",
+];
+
+impl Jobs {
+    /// The key of the job named `name`, given as its key or as its label,
+    /// regardless of case and accents.
+    pub fn find(name: &str) -> Option<&'static str> {
+        let name = fold(name.trim());
+        Jobs::default()
+            .levels()
+            .into_iter()
+            .zip(JOB_LABELS)
+            .find(|((key, _), label)| *key == name.replace(' ', "_") || fold(label) == name)
+            .map(|((key, _), _)| key)
+    }
+
+    pub fn get(name: &str) -> Result<&'static str> {
+        Jobs::find(name).ok_or_else(|| Error::UnknownJob {
+            name: name.to_string(),
+            suggestions: suggestions(name, JOB_LABELS.into_iter()),
+        })
+    }
+
+    pub fn label(job: &str) -> &'static str {
+        Jobs::default()
+            .levels()
+            .into_iter()
+            .zip(JOB_LABELS)
+            .find(|((key, _), _)| *key == job)
+            .map_or("métier inconnu", |(_, label)| label)
+    }
+
+    pub fn level(&self, job: &str) -> Option<u8> {
+        self.levels()
+            .into_iter()
+            .find(|(key, _)| *key == job)
+            .map(|(_, level)| level)
+    }
+
+    pub fn levels(&self) -> [(&'static str, u8); 19] {
+        [
+            ("alchimiste_pharmacien", self.alchimiste_pharmacien),
+            ("alchimiste_artificer", self.alchimiste_artificer),
+            ("chevalier", self.chevalier),
+            ("archer", self.archer),
+            ("combattant", self.combattant),
+            ("escarpe", self.escarpe),
+            ("medecin", self.medecin),
+            ("dresseur", self.dresseur),
+            ("chasseur", self.chasseur),
+            ("agriculteur", self.agriculteur),
+            ("couturier", self.couturier),
+            ("historien", self.historien),
+            ("forgeron", self.forgeron),
+            ("cartographe", self.cartographe),
+            ("cuisinier", self.cuisinier),
+            ("erudit", self.erudit),
+            ("musicien", self.musicien),
+            ("machiniste", self.machiniste),
+            ("ingenieur", self.ingenieur),
+        ]
+    }
+
+    pub fn level_mut(&mut self, job: &str) -> Option<&mut u8> {
+        match job {
+            "alchimiste_pharmacien" => Some(&mut self.alchimiste_pharmacien),
+            "alchimiste_artificer" => Some(&mut self.alchimiste_artificer),
+            "chevalier" => Some(&mut self.chevalier),
+            "archer" => Some(&mut self.archer),
+            "combattant" => Some(&mut self.combattant),
+            "escarpe" => Some(&mut self.escarpe),
+            "medecin" => Some(&mut self.medecin),
+            "dresseur" => Some(&mut self.dresseur),
+            "chasseur" => Some(&mut self.chasseur),
+            "agriculteur" => Some(&mut self.agriculteur),
+            "couturier" => Some(&mut self.couturier),
+            "historien" => Some(&mut self.historien),
+            "forgeron" => Some(&mut self.forgeron),
+            "cartographe" => Some(&mut self.cartographe),
+            "cuisinier" => Some(&mut self.cuisinier),
+            "erudit" => Some(&mut self.erudit),
+            "musicien" => Some(&mut self.musicien),
+            "machiniste" => Some(&mut self.machiniste),
+            "ingenieur" => Some(&mut self.ingenieur),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Jobs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let fields: Vec<_> = JOB_LABELS
+            .into_iter()
+            .zip(self.levels())
+            .map(|(label, (_, count))| (label, count))
+            .filter(|(_, count)| *count > 0)
+            .map(|(name, count)| format!("{} {}", name, count))
+            .collect();
+
+        if fields.is_empty() {
+            write!(f, "Aucun métier")
+        } else {
+            write!(f, "{}", fields.join(", "))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Energy {
+    pub physical: Physical,
+    pub magical: Vec<Magic>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Physical {
+    pub actual_energy: u8,
+    pub energy: u8,
+}
+
+impl fmt::Display for Physical {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.actual_energy, self.energy)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Magic {
+    pub name: String,
+    pub actual_energy: u8,
+    pub energy: u8,
+}
+
+impl fmt::Display for Magic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, " {} {}/{}", self.name, self.actual_energy, self.energy)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Race {
+    Jiaodan,
+    JiaodanHumain,
+    JiaodanDragon,
+    Marwoeth,
+    Demon,
+    Elfe,
+    Ange,
+    FerosumPassif,
+    FerosumExtreme,
+    Horya,
+    Humain,
+    Gwisin,
+    Stens,
+}
+
+impl fmt::Display for Race {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Race::Jiaodan => write!(f, "Jiaodan"),
+            Race::JiaodanHumain => write!(f, "Jiaodan (forme humaine)"),
+            Race::JiaodanDragon => write!(f, "Jiaodan (forme dragon)"),
+            Race::Marwoeth => write!(f, "Marwoeth"),
+            Race::Demon => write!(f, "Demon"),
+            Race::Elfe => write!(f, "Elfe"),
+            Race::Ange => write!(f, "Ange"),
+            Race::FerosumPassif => write!(f, "Ferosum Passif"),
+            Race::FerosumExtreme => write!(f, "Ferosum Extreme"),
+            Race::Horya => write!(f, "Horya"),
+            Race::Humain => write!(f, "Humain"),
+            Race::Gwisin => write!(f, "Gwisin"),
+            Race::Stens => write!(f, "Stens"),
+        }
+    }
+}
+
+impl Race {
+    pub const ALL: [Race; 13] = [
+        Race::Jiaodan,
+        Race::JiaodanHumain,
+        Race::JiaodanDragon,
+        Race::Marwoeth,
+        Race::Demon,
+        Race::Elfe,
+        Race::Ange,
+        Race::FerosumPassif,
+        Race::FerosumExtreme,
+        Race::Horya,
+        Race::Humain,
+        Race::Gwisin,
+        Race::Stens,
+    ];
+
+    pub fn key(&self) -> &'static str {
+        match self {
+            Race::Jiaodan => "Jiaodan",
+            Race::JiaodanHumain => "JiaodanHumain",
+            Race::JiaodanDragon => "JiaodanDragon",
+            Race::Marwoeth => "Marwoeth",
+            Race::Demon => "Demon",
+            Race::Elfe => "Elfe",
+            Race::Ange => "Ange",
+            Race::FerosumPassif => "FerosumPassif",
+            Race::FerosumExtreme => "FerosumExtreme",
+            Race::Horya => "Horya",
+            Race::Humain => "Humain",
+            Race::Gwisin => "Gwisin",
+            Race::Stens => "Stens",
+        }
+    }
+
+    /// The entries of the race table this race draws its stats from. A
+    /// Jiaodan fights in its human form unless stated otherwise.
+    pub fn forms(&self) -> &'static [Race] {
+        match self {
+            Race::Jiaodan => &[Race::JiaodanHumain, Race::JiaodanDragon],
+            Race::JiaodanHumain => &[Race::JiaodanHumain],
+            Race::JiaodanDragon => &[Race::JiaodanDragon],
+            Race::Marwoeth => &[Race::Marwoeth],
+            Race::Demon => &[Race::Demon],
+            Race::Elfe => &[Race::Elfe],
+            Race::Ange => &[Race::Ange],
+            Race::FerosumPassif => &[Race::FerosumPassif],
+            Race::FerosumExtreme => &[Race::FerosumExtreme],
+            Race::Horya => &[Race::Horya],
+            Race::Humain => &[Race::Humain],
+            Race::Gwisin => &[Race::Gwisin],
+            Race::Stens => &[Race::Stens],
+        }
+    }
+
+    pub fn combat_form(&self) -> Race {
+        self.forms()[0]
+    }
+}
+
+impl FromStr for Race {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Race::ALL
+            .into_iter()
+            .find(|race| {
+                race.key().eq_ignore_ascii_case(s) || race.to_string().eq_ignore_ascii_case(s)
+            })
+            .ok_or_else(|| format!("race inconnue: {}", s))
+    }
+}
+
+// Ordered from the lowest rank to the highest.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum Rank {
+    F,
+    E,
+    D,
+    C,
+    B,
+    A,
+    S,
+}
+
+impl fmt::Display for Rank {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Rank {
+    pub const ALL: [Rank; 7] = [
+        Rank::F,
+        Rank::E,
+        Rank::D,
+        Rank::C,
+        Rank::B,
+        Rank::A,
+        Rank::S,
+    ];
+
+    /// The rank a promotion leads to, none above S.
+    pub fn next(&self) -> Option<Rank> {
+        Rank::ALL.get(*self as usize + 1).copied()
+    }
+}
+
+impl FromStr for Rank {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rank::ALL
+            .into_iter()
+            .find(|rank| rank.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| {
+                let ranks: Vec<String> = Rank::ALL.iter().map(Rank::to_string).collect();
+                format!("rang inconnu: {} (rangs valides: {})", s, ranks.join(", "))
+            })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Health {
+    pub state: HealthState,
+    pub description: String,
+}
+
+// Ordered from unhurt to dead.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum HealthState {
+    Aucune,
+    Important,
+    DeathDoor,
+    Mort,
+}
+
+impl fmt::Display for HealthState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HealthState::Aucune => write!(f, "Aucune"),
+            HealthState::Important => write!(f, "Important"),
+            HealthState::DeathDoor => write!(f, "Death door"),
+            HealthState::Mort => write!(f, "Mort"),
+        }
+    }
+}
+
+impl HealthState {
+    pub fn key(&self) -> &'static str {
+        match self {
+            HealthState::Aucune => "Aucune",
+            HealthState::Important => "Important",
+            HealthState::DeathDoor => "DeathDoor",
+            HealthState::Mort => "Mort",
+        }
+    }
+}
+
+impl FromStr for HealthState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            HealthState::Aucune,
+            HealthState::Important,
+            HealthState::DeathDoor,
+            HealthState::Mort,
+        ]
+        .into_iter()
+        .find(|state| state.key().eq_ignore_ascii_case(s))
+        .ok_or_else(|| format!("état de santé inconnu: {}", s))
+    }
+}
+
+pub fn get_adventurers(repo: &dyn AdventurerRepository) -> Result<Vec<Adventurer>> {
+    Ok(repo.all()?)
+}
+
+pub fn get_adventurer(repo: &dyn AdventurerRepository, name: &str) -> Result<Adventurer> {
+    repo.find(name)?
+        .ok_or_else(|| Error::UnknownAdventurer(name.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> XpConfig {
+        XpConfig {
+            base: 100,
+            growth: 1.,
+            level_caps: vec![(Rank::F, 3), (Rank::E, 5)],
+        }
+    }
+
+    fn adventurer() -> Adventurer {
+        Adventurer::new("Arthur", "Humain", "F", None).unwrap()
+    }
+
+    #[test]
+    fn gain_xp_levels_up_as_long_as_there_is_enough() {
+        let mut arthur = adventurer();
+        assert_eq!(arthur.gain_xp(99, &settings()), 0);
+        assert_eq!((arthur.level, arthur.xp), (1, 99));
+        // 100 to reach level 2, then 200 to reach level 3.
+        assert_eq!(arthur.gain_xp(250, &settings()), 2);
+        assert_eq!((arthur.level, arthur.xp), (3, 49));
+    }
+
+    #[test]
+    fn xp_is_kept_at_the_rank_cap_and_spent_after_a_promotion() {
+        let mut arthur = adventurer();
+        assert_eq!(arthur.gain_xp(1000, &settings()), 2);
+        assert_eq!((arthur.level, arthur.xp), (3, 700));
+        assert_eq!(arthur.level_up(&settings()), 0);
+
+        arthur.rank = Rank::E;
+        // 300 to reach level 4, then 400 to reach level 5, the cap of E.
+        assert_eq!(arthur.level_up(&settings()), 2);
+        assert_eq!((arthur.level, arthur.xp), (5, 0));
+    }
+
+    #[test]
+    fn rank_and_level_are_not_editable_fields() {
+        let mut arthur = adventurer();
+        assert!(arthur.set_field("rang", "E").is_err());
+        assert!(arthur.set_field("niveau", "50").is_err());
+        assert_eq!((arthur.rank, arthur.level), (Rank::F, 1));
+        arthur.set_field("quetes", "3").unwrap();
+        assert_eq!(arthur.quests, 3);
+    }
+}
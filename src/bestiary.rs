@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::adventurer::LEVEL_MAX;
+use crate::data::read_json;
+use crate::error::{Error, Result};
+use crate::names::{fold, suggestions};
+use crate::stats::{calc_stats, IvStats, Stats, StatsRace};
+
+/// Either stats that never change, or base stats that grow with the level
+/// through the same formula as the adventurers'.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum CreatureStats {
+    Scaled {
+        base: StatsRace,
+        #[serde(default)]
+        iv: IvStats,
+    },
+    Fixed {
+        stats: Stats,
+    },
+}
+
+/// One monster or NPC of BESTIARY_JSON.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Creature {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    #[serde(default)]
+    pub image_url: Option<String>,
+    /// Level it is met at unless the game master says otherwise.
+    pub level: u8,
+    #[serde(flatten)]
+    pub stats: CreatureStats,
+    #[serde(default)]
+    pub traits: Vec<String>,
+}
+
+impl Creature {
+    pub fn is_scaled(&self) -> bool {
+        matches!(self.stats, CreatureStats::Scaled { .. })
+    }
+
+    /// Its stats at `level`, which fixed stats ignore.
+    pub fn stats(&self, level: u8) -> Stats {
+        match &self.stats {
+            CreatureStats::Scaled { base, iv } => calc_stats(*iv, level, base, None),
+            CreatureStats::Fixed { stats } => stats.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct Bestiary {
+    pub creatures: Vec<Creature>,
+}
+
+impl Bestiary {
+    /// Reads BESTIARY_JSON, refusing duplicated names, levels out of range
+    /// and fixed stats that are not positive.
+    pub fn load(path: &Path) -> Result<Bestiary> {
+        let bestiary: Bestiary = read_json(path)?;
+        let mut names = HashSet::new();
+        let mut problems = Vec::new();
+
+        for creature in &bestiary.creatures {
+            if !names.insert(fold(&creature.name)) {
+                problems.push(format!(
+                    "la créature {} apparaît plusieurs fois",
+                    creature.name
+                ));
+            }
+            if !(1..=LEVEL_MAX).contains(&creature.level) {
+                problems.push(format!(
+                    "le niveau de {} doit être entre 1 et {}",
+                    creature.name, LEVEL_MAX
+                ));
+            }
+            if let CreatureStats::Fixed { stats } = &creature.stats {
+                let values = [
+                    stats.force,
+                    stats.resistance,
+                    stats.vitesse,
+                    stats.force_magique,
+                    stats.resistance_magique,
+                ];
+                if values.iter().any(|value| *value <= 0.) {
+                    problems.push(format!(
+                        "les statistiques de {} doivent être positives",
+                        creature.name
+                    ));
+                }
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(bestiary),
+            false => Err(Error::invalid_data(path, problems.join("\n"))),
+        }
+    }
+
+    /// Names match regardless of case and accents, like weapons.
+    pub fn find(&self, name: &str) -> Option<&Creature> {
+        let name = fold(name);
+        self.creatures.iter().find(|c| fold(&c.name) == name)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Creature> {
+        self.find(name).ok_or_else(|| Error::UnknownCreature {
+            name: name.to_string(),
+            suggestions: self.suggestions(name),
+        })
+    }
+
+    pub fn suggestions(&self, name: &str) -> Vec<String> {
+        suggestions(name, self.creatures.iter().map(|c| c.name.as_str()))
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use danjon_bot::bestiary::Bestiary;
+use danjon_bot::effects::EffectCatalogue;
+use danjon_bot::error::Result;
+use danjon_bot::jobs::JobCatalogue;
+use danjon_bot::ranks::RankTable;
+use danjon_bot::stats::{DiffStats, RaceTable};
+use danjon_bot::weapons::WeaponCatalogue;
+use danjon_bot::weather::Meteos;
+
+use crate::commands::args::Args;
+use crate::commands::{CommandSpec, Env, Reply};
+
+pub const VERIFIER: CommandSpec = CommandSpec {
+    name: "verifier",
+    aliases: &["vérifier"],
+    description: "Vérifie les fichiers de données sur le disque",
+    args: &[],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: true,
+    run: Some(run_verifier),
+};
+
+pub const RECHARGER: CommandSpec = CommandSpec {
+    name: "recharger",
+    aliases: &["reload"],
+    description: "Recharge les fichiers de données sans redémarrer le bot",
+    args: &[],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: true,
+    run: Some(run_recharger),
+};
+
+fn check<T>(name: &str, loaded: Result<T>) -> (bool, String) {
+    match loaded {
+        Ok(_) => (true, format!("{}: OK", name)),
+        Err(why) => (false, format!("{}: {}", name, why)),
+    }
+}
+
+fn check_optional<T>(
+    name: &str,
+    path: &Option<PathBuf>,
+    load: fn(&Path) -> Result<T>,
+) -> (bool, String) {
+    match path {
+        Some(path) => check(name, load(path)),
+        None => (
+            true,
+            format!("{}: aucun fichier configuré, catalogue vide", name),
+        ),
+    }
+}
+
+/// Reads the data files again from disk so that an edit can be checked
+/// before `?recharger`. The data in use is left untouched.
+pub fn verify(env: &Env) -> Result<Reply> {
+    let paths = &env.config.data;
+    let checks = [
+        check("Races", RaceTable::load(&paths.races)),
+        check("Écarts de stats", DiffStats::load(&paths.diff_stats)),
+        check("Météo", Meteos::load(&paths.meteo)),
+        check_optional("Armes", &paths.weapons, WeaponCatalogue::load),
+        check_optional("Bestiaire", &paths.bestiary, Bestiary::load),
+        check_optional("Effets", &paths.effects, EffectCatalogue::load),
+        check_optional("Rangs", &paths.ranks, RankTable::load),
+        check_optional("Métiers", &paths.jobs, JobCatalogue::load),
+    ];
+    let report: Vec<&str> = checks.iter().map(|(_, line)| line.as_str()).collect();
+    let report = format!("```\n{}\n```", report.join("\n"));
+
+    Ok(match checks.iter().all(|(ok, _)| *ok) {
+        true => Reply::text(report),
+        false => Reply::error("Données invalides", report),
+    })
+}
+
+pub fn reload(env: &Env) -> Result<Reply> {
+    env.game_data.reload()?;
+    info!("Game data reloaded");
+
+    Ok(Reply::text("Données rechargées"))
+}
+
+fn run_verifier(env: &Env, _: &Args) -> Result<Reply> {
+    verify(env)
+}
+
+fn run_recharger(env: &Env, _: &Args) -> Result<Reply> {
+    reload(env)
+}
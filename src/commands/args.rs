@@ -0,0 +1,396 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use danjon_bot::adventurer::Race;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArgKind {
+    Text,
+    Integer,
+    Race,
+    /// Swallows every remaining token, joined with spaces.
+    Rest,
+    /// A word that may be written for readability, like `vs` in `?roll`,
+    /// and is skipped when absent.
+    Keyword,
+}
+
+pub struct ArgSpec {
+    pub name: &'static str,
+    pub kind: ArgKind,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+pub struct FlagSpec {
+    pub name: &'static str,
+    pub value: &'static str,
+    pub description: &'static str,
+    /// A scoped flag applies to the positional argument written just before
+    /// it, like `-weapon` in `?roll Arthur -weapon Hache Lyra`.
+    pub scoped: bool,
+}
+
+impl FlagSpec {
+    /// Flags named like `niv=` carry their value in the same token.
+    fn inline(&self) -> bool {
+        self.name.ends_with('=')
+    }
+
+    pub fn syntax(&self) -> String {
+        match self.inline() {
+            true => format!("{}<{}>", self.name, self.value),
+            false => format!("{} <{}>", self.name, self.value),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct UsageError(pub String);
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Args {
+    values: HashMap<&'static str, String>,
+    flags: HashMap<(&'static str, &'static str), String>,
+}
+
+impl Args {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Required arguments are checked by `parse`, so a missing one is a bug
+    /// in the command declaration rather than a user error.
+    pub fn required(&self, name: &str) -> &str {
+        self.get(name)
+            .unwrap_or_else(|| panic!("argument {} is not declared as required", name))
+    }
+
+    /// Value of a scoped flag for `arg`, or of a global flag when `arg` is "".
+    pub fn flag(&self, flag: &str, arg: &str) -> Option<&str> {
+        self.flags
+            .iter()
+            .find(|((f, a), _)| *f == flag && *a == arg)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets an argument given by name rather than by position, as slash
+    /// command options are, checked like those of `parse`.
+    pub fn insert(&mut self, spec: &'static ArgSpec, value: String) -> Result<(), UsageError> {
+        check_kind(spec, &value)?;
+        self.values.insert(spec.name, value);
+        Ok(())
+    }
+
+    /// Sets `flag` for the argument named `arg`, or globally when `arg` is "".
+    pub fn insert_flag(&mut self, flag: &'static FlagSpec, arg: &'static str, value: String) {
+        self.flags.insert((flag.name, arg), value);
+    }
+
+    pub fn check_required(&self, args: &[ArgSpec]) -> Result<(), UsageError> {
+        match args
+            .iter()
+            .find(|spec| spec.required && !self.values.contains_key(spec.name))
+        {
+            Some(missing) => Err(UsageError(format!("argument manquant: <{}>", missing.name))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Splits on whitespace, except inside double quotes so that
+/// `"Loup géant"` is one token. An unclosed quote runs to the end.
+pub fn tokenize(content: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    // Tells `""` apart from no token at all.
+    let mut started = false;
+    for c in content.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    tokens.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        tokens.push(current);
+    }
+
+    tokens
+}
+
+pub fn usage(path: &str, args: &[ArgSpec], flags: &[FlagSpec]) -> String {
+    let scoped: Vec<String> = flags
+        .iter()
+        .filter(|f| f.scoped)
+        .map(|f| format!(" [{}]", f.syntax()))
+        .collect();
+    let mut res = path.to_string();
+    for arg in args {
+        let name = match arg.kind {
+            ArgKind::Rest => format!("{}...", arg.name),
+            _ => arg.name.to_string(),
+        };
+        match (arg.kind, arg.required) {
+            (ArgKind::Keyword, _) => {
+                res.push_str(&format!(" [{}]", name));
+                continue;
+            }
+            (_, true) => res.push_str(&format!(" <{}>", name)),
+            (_, false) => res.push_str(&format!(" [{}]", name)),
+        }
+        res.push_str(&scoped.concat());
+    }
+    for flag in flags.iter().filter(|f| !f.scoped) {
+        res.push_str(&format!(" [{}]", flag.syntax()));
+    }
+
+    res
+}
+
+fn check_kind(spec: &ArgSpec, value: &str) -> Result<(), UsageError> {
+    match spec.kind {
+        ArgKind::Integer => value.parse::<i64>().map(|_| ()).map_err(|_| {
+            UsageError(format!(
+                "`{}` doit être un nombre entier (reçu: {})",
+                spec.name, value
+            ))
+        }),
+        ArgKind::Race => value.parse::<Race>().map(|_| ()).map_err(|why| {
+            let races: Vec<&str> = Race::ALL.iter().map(Race::key).collect();
+            UsageError(format!("{} (races valides: {})", why, races.join(", ")))
+        }),
+        ArgKind::Text | ArgKind::Rest | ArgKind::Keyword => Ok(()),
+    }
+}
+
+pub fn parse(
+    tokens: &[String],
+    args: &'static [ArgSpec],
+    flags: &'static [FlagSpec],
+) -> Result<Args, UsageError> {
+    let mut res = Args::default();
+    let mut positionals = args.iter();
+    let mut last: Option<&'static str> = None;
+    let mut tokens = tokens.iter();
+
+    while let Some(token) = tokens.next() {
+        let flag = flags.iter().find(|f| match f.inline() {
+            true => token.starts_with(f.name),
+            false => f.name == token.as_str(),
+        });
+        if let Some(flag) = flag {
+            let value = match flag.inline() {
+                true => Some(token[flag.name.len()..].to_string()).filter(|v| !v.is_empty()),
+                false => tokens.next().cloned(),
+            };
+            let value = value.ok_or_else(|| {
+                UsageError(format!(
+                    "l'option {} attend une valeur <{}>",
+                    flag.name, flag.value
+                ))
+            })?;
+            let scope = match flag.scoped {
+                true => last.ok_or_else(|| {
+                    UsageError(format!(
+                        "l'option {} doit suivre l'argument qu'elle modifie",
+                        flag.name
+                    ))
+                })?,
+                false => "",
+            };
+            res.flags.insert((flag.name, scope), value);
+            continue;
+        }
+        if token.starts_with('-') && token.parse::<f64>().is_err() {
+            return Err(UsageError(format!("option inconnue: {}", token)));
+        }
+        let mut spec = positionals
+            .next()
+            .ok_or_else(|| UsageError(format!("argument en trop: {}", token)))?;
+        if spec.kind == ArgKind::Keyword {
+            if spec.name.eq_ignore_ascii_case(token) {
+                continue;
+            }
+            spec = positionals
+                .next()
+                .ok_or_else(|| UsageError(format!("argument en trop: {}", token)))?;
+        }
+        let value = match spec.kind {
+            ArgKind::Rest => std::iter::once(token)
+                .chain(tokens.by_ref())
+                .map(String::as_str)
+                .collect::<Vec<_>>()
+                .join(" "),
+            _ => token.clone(),
+        };
+        check_kind(spec, &value)?;
+        res.values.insert(spec.name, value);
+        last = Some(spec.name);
+    }
+
+    res.check_required(args)?;
+
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ARGS: &[ArgSpec] = &[
+        ArgSpec {
+            name: "premier",
+            kind: ArgKind::Text,
+            required: true,
+            description: "",
+        },
+        ArgSpec {
+            name: "vs",
+            kind: ArgKind::Keyword,
+            required: false,
+            description: "",
+        },
+        ArgSpec {
+            name: "second",
+            kind: ArgKind::Text,
+            required: true,
+            description: "",
+        },
+        ArgSpec {
+            name: "manches",
+            kind: ArgKind::Integer,
+            required: false,
+            description: "",
+        },
+    ];
+
+    const FLAGS: &[FlagSpec] = &[
+        FlagSpec {
+            name: "-weapon",
+            value: "arme",
+            description: "",
+            scoped: true,
+        },
+        FlagSpec {
+            name: "niv=",
+            value: "niveau",
+            description: "",
+            scoped: true,
+        },
+        FlagSpec {
+            name: "-seed",
+            value: "graine",
+            description: "",
+            scoped: false,
+        },
+    ];
+
+    const REST: &[ArgSpec] = &[
+        ArgSpec {
+            name: "camp",
+            kind: ArgKind::Text,
+            required: true,
+            description: "",
+        },
+        ArgSpec {
+            name: "noms",
+            kind: ArgKind::Rest,
+            required: true,
+            description: "",
+        },
+    ];
+
+    fn parse_str(content: &str, args: &'static [ArgSpec]) -> Result<Args, UsageError> {
+        parse(&tokenize(content), args, FLAGS)
+    }
+
+    #[test]
+    fn tokenize_keeps_quoted_spaces() {
+        assert_eq!(
+            tokenize(r#"Arthur  vs "Loup géant" niv=3"#),
+            ["Arthur", "vs", "Loup géant", "niv=3"]
+        );
+    }
+
+    #[test]
+    fn tokenize_keeps_empty_quotes_and_runs_unclosed_ones_to_the_end() {
+        assert_eq!(tokenize(r#"a "" b"#), ["a", "", "b"]);
+        assert_eq!(tokenize(r#"a "b c"#), ["a", "b c"]);
+        assert!(tokenize("   ").is_empty());
+    }
+
+    #[test]
+    fn parse_skips_the_optional_keyword() {
+        for content in ["Arthur vs Lyra", "Arthur Lyra"] {
+            let args = parse_str(content, ARGS).unwrap();
+            assert_eq!(args.get("premier"), Some("Arthur"));
+            assert_eq!(args.get("second"), Some("Lyra"));
+            assert_eq!(args.get("manches"), None);
+        }
+    }
+
+    #[test]
+    fn parse_scopes_flags_to_the_argument_before_them() {
+        let args = parse_str("Arthur -weapon Hache vs Gobelin niv=3 -seed 42", ARGS).unwrap();
+        assert_eq!(args.flag("-weapon", "premier"), Some("Hache"));
+        assert_eq!(args.flag("-weapon", "second"), None);
+        assert_eq!(args.flag("niv=", "second"), Some("3"));
+        assert_eq!(args.flag("-seed", ""), Some("42"));
+    }
+
+    #[test]
+    fn parse_joins_the_rest() {
+        let args = parse_str("groupe Arthur Lyra Gobelin", REST).unwrap();
+        assert_eq!(args.get("camp"), Some("groupe"));
+        assert_eq!(args.get("noms"), Some("Arthur Lyra Gobelin"));
+    }
+
+    #[test]
+    fn parse_reports_usage_errors() {
+        let error = |content| parse_str(content, ARGS).unwrap_err().0;
+        assert_eq!(error("Arthur"), "argument manquant: <second>");
+        assert_eq!(error("Arthur Lyra 3 4"), "argument en trop: 4");
+        assert_eq!(
+            error("Arthur Lyra trois"),
+            "`manches` doit être un nombre entier (reçu: trois)"
+        );
+        assert_eq!(error("Arthur -vite Lyra"), "option inconnue: -vite");
+        assert_eq!(
+            error("-weapon Hache Arthur Lyra"),
+            "l'option -weapon doit suivre l'argument qu'elle modifie"
+        );
+        assert_eq!(
+            error("Arthur niv= Lyra"),
+            "l'option niv= attend une valeur <niveau>"
+        );
+        assert_eq!(
+            error("Arthur Lyra -seed"),
+            "l'option -seed attend une valeur <graine>"
+        );
+    }
+
+    #[test]
+    fn parse_takes_negative_numbers_as_values() {
+        let args = parse_str("Arthur Lyra -2", ARGS).unwrap();
+        assert_eq!(args.get("manches"), Some("-2"));
+    }
+}
@@ -0,0 +1,103 @@
+use serenity::builder::CreateEmbed;
+
+use danjon_bot::adventurer::LEVEL_MAX;
+use danjon_bot::bestiary::Creature;
+use danjon_bot::error::{Error, Result};
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{CommandSpec, Env, Reply};
+
+pub const BESTIAIRE: CommandSpec = CommandSpec {
+    name: "bestiaire",
+    aliases: &["creature", "créature"],
+    description: "Affiche la fiche d'une créature, ou la liste du bestiaire",
+    args: &[
+        ArgSpec {
+            name: "nom",
+            kind: ArgKind::Text,
+            required: false,
+            description: "Nom de la créature",
+        },
+        ArgSpec {
+            name: "niveau",
+            kind: ArgKind::Integer,
+            required: false,
+            description: "Niveau auquel la rencontrer, le sien par défaut",
+        },
+    ],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run),
+};
+
+fn creature_embed(creature: &Creature, level: u8) -> CreateEmbed {
+    let level_note = match creature.is_scaled() {
+        true => format!("{}", level),
+        false => format!("{} (statistiques fixes)", level),
+    };
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(&creature.name)
+        .description(&creature.description)
+        .field("__Niveau__", level_note, true)
+        .field("__Statistiques__", creature.stats(level).to_string(), false);
+    if !creature.traits.is_empty() {
+        embed.field("__Traits__", creature.traits.join(", "), false);
+    }
+    if let Some(image_url) = &creature.image_url {
+        embed.image(image_url);
+    }
+
+    embed
+}
+
+/// The sheet of a creature at `level`, its own by default, or the list of
+/// the bestiary when no name is given.
+pub fn creature(env: &Env, name: Option<&str>, level: Option<u8>) -> Result<Reply> {
+    let bestiary = &env.data.bestiary;
+    let name = match name {
+        Some(name) => name,
+        None if bestiary.creatures.is_empty() => {
+            return Ok(Reply::text("Le bestiaire est vide."));
+        }
+        None => {
+            let names: Vec<String> = bestiary
+                .creatures
+                .iter()
+                .map(|c| format!("{} (niv. {})", c.name, c.level))
+                .collect();
+            return Ok(Reply::text(format!(
+                "Créatures du bestiaire: {}",
+                names.join(", ")
+            )));
+        }
+    };
+    let creature = bestiary.get(name)?;
+    let level = level.unwrap_or(creature.level);
+    if !(1..=LEVEL_MAX).contains(&level) {
+        return Err(Error::Validation(format!(
+            "le niveau doit être entre 1 et {} (reçu: {})",
+            LEVEL_MAX, level
+        )));
+    }
+
+    Ok(Reply {
+        embed: Some(creature_embed(creature, level)),
+        ..Default::default()
+    })
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    let level = match args.get("niveau") {
+        Some(level) => Some(level.parse().map_err(|_| {
+            Error::Validation(format!(
+                "le niveau doit être entre 1 et {} (reçu: {})",
+                LEVEL_MAX, level
+            ))
+        })?),
+        None => None,
+    };
+    creature(env, args.get("nom"), level)
+}
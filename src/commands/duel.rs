@@ -0,0 +1,287 @@
+use serenity::builder::CreateEmbed;
+use std::cmp::Ordering;
+
+use danjon_bot::adventurer::{get_adventurer, Adventurer, HealthState};
+use danjon_bot::config::DuelConfig;
+use danjon_bot::effects::Restriction;
+use danjon_bot::error::{Error, Result};
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::roll::{
+    armed_entity, ensure_allowed, record_roll, wear_weapon, DiffStatsState, Entities,
+};
+use crate::commands::{tail_lines, CommandSpec, Env, Reply};
+
+pub const DUEL: CommandSpec = CommandSpec {
+    name: "duel",
+    aliases: &["combat"],
+    description: "Enchaîne les échanges entre deux aventuriers jusqu'à la fin du combat",
+    args: &[
+        ArgSpec {
+            name: "premier",
+            kind: ArgKind::Text,
+            required: true,
+            description: "Premier aventurier",
+        },
+        ArgSpec {
+            name: "second",
+            kind: ArgKind::Text,
+            required: true,
+            description: "Second aventurier",
+        },
+        ArgSpec {
+            name: "manches",
+            kind: ArgKind::Integer,
+            required: false,
+            description: "Nombre d'échanges, selon la configuration par défaut",
+        },
+    ],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run),
+};
+
+// Characters of the round log shown in the embed description, which is
+// limited to 4096, leaving room for the outcome.
+const DUEL_LOG_MAX: usize = 3800;
+
+struct Duellist {
+    adventurer: Adventurer,
+    initial_state: HealthState,
+    /// Wound points, starting from those of the initial state.
+    wounds: u32,
+    hits: u32,
+    best_hit: Option<DiffStatsState>,
+    /// Wear taken by the weapon they fought with.
+    usure: f32,
+}
+
+impl Duellist {
+    fn new(env: &Env, adventurer: Adventurer) -> Self {
+        Duellist {
+            initial_state: adventurer.health.state,
+            wounds: env.config.duel.wounds(adventurer.health.state),
+            adventurer,
+            hits: 0,
+            best_hit: None,
+            usure: 0.,
+        }
+    }
+
+    fn summary(&self) -> String {
+        let mut res = format!(
+            "Touches: {}\nMeilleur coup: {}\nSanté: {} → {}\nPoints de blessure: {}",
+            self.hits,
+            self.best_hit
+                .map_or("aucun".to_string(), |tier| tier.to_string()),
+            self.initial_state,
+            self.adventurer.health.state,
+            self.wounds
+        );
+        if let Some(weapon) = &self.adventurer.weapon {
+            res.push_str(&format!("\nArme: {}", weapon));
+        }
+        res
+    }
+}
+
+/// Saves what a fight did to `fought`, the copy of a sheet it was played
+/// on: `usure` taken off the weapon they fought with, and their health when
+/// it got worse. The sheet is read again with the write, so that anything
+/// saved since it was copied is kept.
+pub fn save_fight(env: &Env, fought: &Adventurer, usure: f32) -> Result<()> {
+    env.adventurers.modify(&fought.name, &mut |adventurer| {
+        if let (Some(weapon), Some(used)) = (adventurer.weapon.as_mut(), &fought.weapon) {
+            if weapon.name == used.name {
+                weapon.wear(usure);
+            }
+        }
+        if fought.health.state > adventurer.health.state {
+            adventurer.health = fought.health.clone();
+        }
+        true
+    })?;
+
+    Ok(())
+}
+
+/// Adds the wound points of a `hit` dealt by `by` and escalates the
+/// adventurer's health with them. Returns the new state when it changed.
+pub fn wound(
+    settings: &DuelConfig,
+    adventurer: &mut Adventurer,
+    wounds: &mut u32,
+    hit: DiffStatsState,
+    by: &str,
+) -> Option<HealthState> {
+    *wounds += hit.wounds();
+    let state = settings.health(*wounds);
+    if state <= adventurer.health.state {
+        return None;
+    }
+    adventurer.health.state = state;
+    adventurer.health.description = format!("Blessé au combat contre {}", by);
+
+    Some(state)
+}
+
+/// Plays up to `rounds` exchanges between two adventurers. Every exchange
+/// lost wounds the loser by the tier of the hit and wears their weapon;
+/// their `Health.state` escalates through the `[duel]` thresholds and the
+/// duel stops as soon as one of them dies. Both sheets are saved at the end,
+/// through `save_fight`.
+pub fn duel(env: &Env, first: &str, second: &str, rounds: Option<u8>) -> Result<Reply> {
+    let settings = &env.config.duel;
+    let rounds = rounds.unwrap_or(settings.rounds);
+    if rounds == 0 || rounds > settings.max_rounds {
+        return Err(Error::Validation(format!(
+            "un duel dure de 1 à {} manches (reçu: {})",
+            settings.max_rounds, rounds
+        )));
+    }
+    if first == second {
+        return Err(Error::Validation(format!(
+            "{} ne peut pas se battre contre lui-même",
+            first
+        )));
+    }
+
+    let repo = env.adventurers.as_ref();
+    let mut duellists = [
+        Duellist::new(env, get_adventurer(repo, first)?),
+        Duellist::new(env, get_adventurer(repo, second)?),
+    ];
+    for duellist in &duellists {
+        ensure_allowed(env, &duellist.adventurer, Restriction::Combat)?;
+        if duellist.initial_state == HealthState::Mort {
+            return Err(Error::Validation(format!(
+                "{} est mort et ne peut plus se battre",
+                duellist.adventurer.name
+            )));
+        }
+    }
+
+    let mut log = String::new();
+    for round in 1..=rounds {
+        // Broken weapons are only worth mentioning once.
+        let mut notes = Vec::new();
+        let entities = Entities {
+            entity_one: armed_entity(env, &duellists[0].adventurer, None, &mut notes)?,
+            entity_second: armed_entity(env, &duellists[1].adventurer, None, &mut notes)?,
+        };
+        if round == 1 {
+            for note in notes {
+                log.push_str(&format!("{}\n", note));
+            }
+        }
+
+        let (record, result) = record_roll(env, &entities)?;
+        let (winner, loser, loser_equipped) = match result.first_won {
+            true => (0, 1, entities.entity_second.equipped),
+            false => (1, 0, entities.entity_one.equipped),
+        };
+        log.push_str(&format!(
+            "**Manche {}** (#{}): {} touche {} avec {} en vitesse, causant {}\n",
+            round,
+            record.id,
+            result.gagnant,
+            result.perdant,
+            result.diff_vitesse,
+            result.diff_force
+        ));
+
+        let attacker = &mut duellists[winner];
+        attacker.hits += 1;
+        if attacker
+            .best_hit
+            .is_none_or(|best| result.diff_force > best)
+        {
+            attacker.best_hit = Some(result.diff_force);
+        }
+
+        let defender = &mut duellists[loser];
+        if loser_equipped {
+            defender.usure += result.usure;
+            if let Some(note) = wear_weapon(&mut defender.adventurer, result.usure) {
+                log.push_str(&format!("{}\n", note));
+            }
+        }
+        let hurt = wound(
+            settings,
+            &mut defender.adventurer,
+            &mut defender.wounds,
+            result.diff_force,
+            &result.gagnant,
+        );
+        if let Some(state) = hurt {
+            log.push_str(&format!(
+                "{} passe à l'état {}\n",
+                defender.adventurer.name, state
+            ));
+        }
+        if defender.adventurer.health.state == HealthState::Mort {
+            break;
+        }
+    }
+
+    for duellist in &duellists {
+        save_fight(env, &duellist.adventurer, duellist.usure)?;
+    }
+
+    let [one, two] = &duellists;
+    let dead = |duellist: &Duellist| duellist.adventurer.health.state == HealthState::Mort;
+    // A kill ends the duel, whoever landed the most hits before it.
+    let outcome = match (dead(one), dead(two), one.hits.cmp(&two.hits)) {
+        (true, false, _) => format!(
+            "Vainqueur: {} ({} est mort)",
+            two.adventurer.name, one.adventurer.name
+        ),
+        (false, true, _) => format!(
+            "Vainqueur: {} ({} est mort)",
+            one.adventurer.name, two.adventurer.name
+        ),
+        (_, _, Ordering::Greater) => format!("Vainqueur: {}", one.adventurer.name),
+        (_, _, Ordering::Less) => format!("Vainqueur: {}", two.adventurer.name),
+        (_, _, Ordering::Equal) => "Égalité".to_string(),
+    };
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!(
+            "Duel: {} contre {}",
+            one.adventurer.name, two.adventurer.name
+        ))
+        .description(format!(
+            "**{}**\n\n{}",
+            outcome,
+            tail_lines(&log, DUEL_LOG_MAX)
+        ))
+        .fields(vec![
+            (&one.adventurer.name, one.summary(), true),
+            (&two.adventurer.name, two.summary(), true),
+        ]);
+
+    Ok(Reply {
+        embed: Some(embed),
+        ..Default::default()
+    })
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    let rounds = match args.get("manches") {
+        Some(rounds) => Some(rounds.parse().map_err(|_| {
+            Error::Validation(format!(
+                "un duel dure de 1 à {} manches (reçu: {})",
+                env.config.duel.max_rounds, rounds
+            ))
+        })?),
+        None => None,
+    };
+    duel(
+        env,
+        args.required("premier"),
+        args.required("second"),
+        rounds,
+    )
+}
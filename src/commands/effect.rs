@@ -0,0 +1,264 @@
+use serenity::prelude::*;
+use std::time::Duration;
+use tracing::{error, info};
+
+use danjon_bot::adventurer::get_adventurer;
+use danjon_bot::config;
+use danjon_bot::data;
+use danjon_bot::effects::{expires_at, ActiveEffect, Effect};
+use danjon_bot::error::{Error, Result};
+use danjon_bot::names::fold;
+use danjon_bot::stats::Stats;
+use danjon_bot::storage;
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{now, send, CommandSpec, Env, Reply};
+
+const NOM: ArgSpec = ArgSpec {
+    name: "nom",
+    kind: ArgKind::Text,
+    required: true,
+    description: "Nom de l'aventurier",
+};
+
+const NOM_EFFET: ArgSpec = ArgSpec {
+    name: "effet",
+    kind: ArgKind::Text,
+    required: true,
+    description: "Nom de l'effet du catalogue, entre guillemets s'il a des espaces",
+};
+
+pub const EFFET: CommandSpec = CommandSpec {
+    name: "effet",
+    aliases: &["effets"],
+    description: "Affiche les effets qui pèsent sur un aventurier",
+    args: &[NOM],
+    flags: &[],
+    subcommands: &[
+        CommandSpec {
+            name: "ajouter",
+            aliases: &[],
+            description: "Place un aventurier sous un effet du catalogue pour une durée",
+            args: &[
+                NOM,
+                NOM_EFFET,
+                ArgSpec {
+                    name: "duree",
+                    kind: ArgKind::Text,
+                    required: true,
+                    description: "Durée de l'effet, par exemple 30m, 2h ou 3j",
+                },
+            ],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_add),
+        },
+        CommandSpec {
+            name: "retirer",
+            aliases: &[],
+            description: "Met fin à un effet avant son terme",
+            args: &[NOM, NOM_EFFET],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_remove),
+        },
+        CommandSpec {
+            name: "catalogue",
+            aliases: &["liste"],
+            description: "Liste les effets du catalogue",
+            args: &[],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: false,
+            run: Some(run_catalogue),
+        },
+    ],
+    default_subcommand: Some("actifs"),
+    game_master: false,
+    run: Some(run),
+};
+
+// How often effects are looked at for drains and expiry.
+const TICK_CHECK_PERIOD: Duration = Duration::from_secs(60);
+
+fn describe(effect: &Effect) -> String {
+    let mut parts = Vec::new();
+    if effect.modificateurs != Stats::default() {
+        let changed: Vec<String> = effect
+            .modificateurs
+            .fields()
+            .iter()
+            .filter(|(_, value)| *value != 0.)
+            .map(|(name, value)| format!("{} {:+}", name, value))
+            .collect();
+        parts.push(changed.join(", "));
+    }
+    match effect.drain {
+        0 => {}
+        drain if drain > 0 => parts.push(format!("-{} énergie physique par tick", drain)),
+        drain => parts.push(format!("+{} énergie physique par tick", -drain)),
+    }
+    for restriction in &effect.restrictions {
+        parts.push(format!("ne peut pas {}", restriction));
+    }
+
+    let mut res = format!("**{}**", effect.name);
+    if !effect.description.is_empty() {
+        res.push_str(&format!(": {}", effect.description));
+    }
+    if !parts.is_empty() {
+        res.push_str(&format!(" ({})", parts.join("; ")));
+    }
+    res
+}
+
+pub fn effects(env: &Env, name: &str) -> Result<Reply> {
+    let adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    if adventurer.effects.is_empty() {
+        return Ok(Reply::text(format!(
+            "{} n'est sous aucun effet.",
+            adventurer.name
+        )));
+    }
+
+    let mut res = format!("Effets de {}:\n", adventurer.name);
+    for active in &adventurer.effects {
+        let effect = match env.data.effects.find(&active.name) {
+            Some(effect) => describe(effect),
+            None => format!("**{}** (retiré du catalogue)", active.name),
+        };
+        res.push_str(&format!("{}, fin <t:{}:R>\n", effect, active.expires_at));
+    }
+
+    Ok(Reply::text(res))
+}
+
+/// Puts `name` under `effect` for `duration`. An effect they are already
+/// under lasts until the later of both ends.
+pub fn add(env: &Env, name: &str, effect: &str, duration: &str) -> Result<Reply> {
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let effect = env.data.effects.get(effect)?;
+    let now = now();
+    let expires_at = expires_at(now, duration).map_err(Error::Validation)?;
+    match adventurer
+        .effects
+        .iter_mut()
+        .find(|e| e.name == effect.name)
+    {
+        Some(active) => active.expires_at = active.expires_at.max(expires_at),
+        None => adventurer.effects.push(ActiveEffect {
+            name: effect.name.clone(),
+            expires_at,
+            last_tick: now,
+        }),
+    }
+    env.adventurers.update(&adventurer)?;
+
+    Ok(Reply::text(format!(
+        "{} est sous l'effet de {}",
+        adventurer.name,
+        describe(effect)
+    )))
+}
+
+pub fn remove(env: &Env, name: &str, effect: &str) -> Result<Reply> {
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let before = adventurer.effects.len();
+    let folded = fold(effect);
+    adventurer
+        .effects
+        .retain(|active| fold(&active.name) != folded);
+    if adventurer.effects.len() == before {
+        return Err(Error::Validation(format!(
+            "{} n'est pas sous l'effet de {}",
+            adventurer.name, effect
+        )));
+    }
+    env.adventurers.update(&adventurer)?;
+
+    Ok(Reply::text(format!(
+        "{} n'est plus sous l'effet de {}",
+        adventurer.name, effect
+    )))
+}
+
+pub fn catalogue(env: &Env) -> Result<Reply> {
+    let effects: Vec<String> = env.data.effects.effects.iter().map(describe).collect();
+    match effects.is_empty() {
+        true => Ok(Reply::text("Le catalogue des effets est vide.")),
+        false => Ok(Reply::text(effects.join("\n"))),
+    }
+}
+
+/// Applies the drains that are due and removes the effects that are over,
+/// then posts what changed to the announcement channel.
+async fn tick_all(ctx: &Context) {
+    let config = config::config(ctx).await;
+    let game_data = data::game_data(ctx).await.get();
+    let repo = storage::adventurers(ctx).await;
+    let adventurers = match repo.all() {
+        Ok(adventurers) => adventurers,
+        Err(why) => {
+            error!("Error listing adventurers for their effects: {:?}", why);
+            return;
+        }
+    };
+
+    let tick = config.effects.tick_minutes as i64 * 60;
+    let mut notes = Vec::new();
+    for adventurer in adventurers.iter().filter(|a| !a.effects.is_empty()) {
+        // The sheet is read again with the write, so that a command saving
+        // it since the listing above is not undone.
+        let mut changes = Vec::new();
+        let saved = repo.modify(&adventurer.name, &mut |adventurer| {
+            let before = adventurer.effects.clone();
+            changes = adventurer.tick_effects(&game_data.effects, now(), tick);
+            !changes.is_empty() || adventurer.effects != before
+        });
+        match saved {
+            Ok(_) => notes.extend(changes),
+            Err(why) => error!("Error saving the effects of {}: {:?}", adventurer.name, why),
+        }
+    }
+
+    match config.effects.announcement_channel {
+        Some(channel_id) if !notes.is_empty() => {
+            send(ctx, channel_id, Reply::text(notes.join("\n"))).await
+        }
+        _ => notes.iter().for_each(|note| info!("{}", note)),
+    }
+}
+
+pub async fn run_effects(ctx: Context) {
+    let mut interval = tokio::time::interval(TICK_CHECK_PERIOD);
+    loop {
+        interval.tick().await;
+        tick_all(&ctx).await;
+    }
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    effects(env, args.required("nom"))
+}
+
+fn run_add(env: &Env, args: &Args) -> Result<Reply> {
+    add(
+        env,
+        args.required("nom"),
+        args.required("effet"),
+        args.required("duree"),
+    )
+}
+
+fn run_remove(env: &Env, args: &Args) -> Result<Reply> {
+    remove(env, args.required("nom"), args.required("effet"))
+}
+
+fn run_catalogue(env: &Env, _: &Args) -> Result<Reply> {
+    catalogue(env)
+}
@@ -0,0 +1,436 @@
+use std::collections::HashMap;
+
+use danjon_bot::adventurer::{get_adventurer, Adventurer, HealthState};
+use danjon_bot::effects::Restriction;
+use danjon_bot::encounter::{Combatant, Encounter, Participant};
+use danjon_bot::error::{Error, Result};
+use danjon_bot::stats::Stats;
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::duel::{save_fight, wound};
+use crate::commands::roll::{
+    armed_entity, creature_entity, ensure_allowed, ensure_unclaimed, improvised_entity,
+    record_roll, unknown_combatant, wear_weapon, Entities, Entity, STAT_FLAGS,
+};
+use crate::commands::{CommandSpec, Env, Reply};
+
+pub const RENCONTRE: CommandSpec = CommandSpec {
+    name: "rencontre",
+    aliases: &["escarmouche"],
+    description: "Affiche la rencontre en cours dans le salon",
+    args: &[],
+    flags: &[],
+    subcommands: &[
+        CommandSpec {
+            name: "creer",
+            aliases: &["créer"],
+            description: "Commence une rencontre dans le salon",
+            args: &[],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_create),
+        },
+        CommandSpec {
+            name: "ajouter",
+            aliases: &[],
+            description: "Ajoute des aventuriers, des créatures ou des combattants improvisés à un camp",
+            args: &[
+                ArgSpec {
+                    name: "camp",
+                    kind: ArgKind::Text,
+                    required: true,
+                    description: "Nom du camp, par exemple groupe ou monstres",
+                },
+                ArgSpec {
+                    name: "noms",
+                    kind: ArgKind::Rest,
+                    required: true,
+                    description: "Participants séparés par des espaces, avec leurs stats comme Brigand:niv=4:for=12",
+                },
+            ],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_add),
+        },
+        CommandSpec {
+            name: "attaquer",
+            aliases: &[],
+            description: "Déclare qui un participant attaque ce tour",
+            args: &[
+                ArgSpec {
+                    name: "attaquant",
+                    kind: ArgKind::Text,
+                    required: true,
+                    description: "Participant qui attaque",
+                },
+                ArgSpec {
+                    name: "cible",
+                    kind: ArgKind::Text,
+                    required: true,
+                    description: "Participant du camp adverse visé",
+                },
+            ],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: false,
+            run: Some(run_declare),
+        },
+        CommandSpec {
+            name: "tour",
+            aliases: &[],
+            description: "Résout les attaques déclarées, des plus rapides aux plus lents",
+            args: &[],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_turn),
+        },
+        CommandSpec {
+            name: "terminer",
+            aliases: &["fin"],
+            description: "Met fin à la rencontre du salon",
+            args: &[],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_end),
+        },
+    ],
+    default_subcommand: Some("etat"),
+    game_master: false,
+    run: Some(run_state),
+};
+
+fn current(env: &Env) -> Result<Encounter> {
+    env.encounters.find(env.channel_id.0)?.ok_or_else(|| {
+        Error::Validation(format!(
+            "aucune rencontre en cours dans ce salon (`{}rencontre creer` pour en commencer une)",
+            env.config.prefix
+        ))
+    })
+}
+
+fn summary(encounter: &Encounter) -> String {
+    let mut res = format!("**Rencontre, tour {}**\n", encounter.turn);
+    for side in encounter.sides() {
+        let members: Vec<String> = encounter
+            .participants
+            .iter()
+            .filter(|p| p.side == side)
+            .map(|p| match (&p.target, p.out) {
+                (_, true) => format!("~~{}~~", p.name),
+                (Some(target), false) => format!("{} → {}", p.name, target),
+                (None, false) => p.name.clone(),
+            })
+            .collect();
+        res.push_str(&format!("{}: {}\n", side, members.join(", ")));
+    }
+    res
+}
+
+pub fn state(env: &Env) -> Result<Reply> {
+    Ok(Reply::text(summary(&current(env)?)))
+}
+
+pub fn create(env: &Env) -> Result<Reply> {
+    if env.encounters.find(env.channel_id.0)?.is_some() {
+        return Err(Error::Validation(
+            "une rencontre est déjà en cours dans ce salon".to_string(),
+        ));
+    }
+    env.encounters.save(&Encounter::new(env.channel_id.0))?;
+
+    Ok(Reply::text(format!(
+        "Rencontre commencée, ajoute des participants avec `{}rencontre ajouter <camp> <noms>`",
+        env.config.prefix
+    )))
+}
+
+// `Nom` alone, or followed by stats as in `Brigand:niv=4:for=12`.
+fn split_stats(token: &str) -> Result<(&str, Vec<(&str, &str)>)> {
+    let mut parts = token.split(':');
+    let name = parts.next().unwrap_or_default();
+    let given = parts
+        .map(|part| {
+            STAT_FLAGS
+                .iter()
+                .find(|flag| part.starts_with(*flag))
+                .map(|flag| (*flag, &part[flag.len()..]))
+                .ok_or_else(|| {
+                    Error::Validation(format!(
+                        "stat inconnue pour {}: {} (stats: {})",
+                        name,
+                        part,
+                        STAT_FLAGS.join(" ")
+                    ))
+                })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok((name, given))
+}
+
+/// Adds participants to `side`, each written as `Nom` or `Nom:niv=4:for=12`:
+/// - an adventurer keeps the wounds of their current health, and cannot
+///   join at death's door;
+/// - a creature of the bestiary joins at its level unless `niv=` is given,
+///   and is numbered when it is already there, as in `Gobelin2`;
+/// - any other name needs stats and joins as an improvised fighter.
+pub fn add(env: &Env, side: &str, names: &[&str]) -> Result<Reply> {
+    let mut encounter = current(env)?;
+    let unhurt = env.config.duel.wounds(HealthState::Aucune);
+    for token in names {
+        let (name, given) = split_stats(token)?;
+        if let Some(adventurer) = env.adventurers.find(name)? {
+            if !given.is_empty() {
+                ensure_unclaimed(env, name)?;
+            }
+            if adventurer.health.state >= HealthState::DeathDoor {
+                return Err(Error::Validation(format!(
+                    "{} est trop blessé pour combattre ({})",
+                    name, adventurer.health.state
+                )));
+            }
+            let wounds = env.config.duel.wounds(adventurer.health.state);
+            encounter.add(&adventurer.name, Combatant::Adventurer, side, wounds)?;
+        } else if let Some(creature) = env.data.bestiary.find(name) {
+            if given.iter().any(|(flag, _)| *flag != "niv=") {
+                ensure_unclaimed(env, name)?;
+            }
+            let level = match given.is_empty() {
+                true => creature.level,
+                false => improvised_entity(name, given)?.level,
+            };
+            let combatant = Combatant::Creature {
+                creature: creature.name.clone(),
+                level,
+            };
+            let name = encounter.free_name(&creature.name);
+            encounter.add(&name, combatant, side, unhurt)?;
+        } else if given.is_empty() {
+            return Err(unknown_combatant(env, name));
+        } else {
+            let entity = improvised_entity(name, given)?;
+            let combatant = Combatant::Improvised {
+                level: entity.level,
+                stats: Stats {
+                    force: entity.force,
+                    resistance: entity.resistance,
+                    vitesse: entity.vitesse,
+                    force_magique: entity.force_magique,
+                    resistance_magique: entity.resistance_magique,
+                },
+            };
+            encounter.add(name, combatant, side, unhurt)?;
+        }
+    }
+    env.encounters.save(&encounter)?;
+
+    Ok(Reply::text(summary(&encounter)))
+}
+
+pub fn declare(env: &Env, attacker: &str, target: &str) -> Result<Reply> {
+    let mut encounter = current(env)?;
+    encounter.declare(attacker, target)?;
+    if encounter.participant(attacker).map(|p| &p.combatant) == Some(&Combatant::Adventurer) {
+        let adventurer = get_adventurer(env.adventurers.as_ref(), attacker)?;
+        ensure_allowed(env, &adventurer, Restriction::Combat)?;
+    }
+    env.encounters.save(&encounter)?;
+
+    Ok(Reply::text(format!(
+        "{} attaquera {} au tour {}",
+        attacker, target, encounter.turn
+    )))
+}
+
+// The stats `participant` fights with, rebuilt from their sheet, the
+// bestiary or the stats they joined with.
+fn participant_entity(
+    env: &Env,
+    participant: &Participant,
+    adventurers: &HashMap<String, Adventurer>,
+    notes: &mut Vec<String>,
+) -> Result<Entity> {
+    match &participant.combatant {
+        Combatant::Adventurer => armed_entity(env, &adventurers[&participant.name], None, notes),
+        Combatant::Creature { creature, level } => {
+            let mut entity = creature_entity(env.data.bestiary.get(creature)?, *level);
+            entity.name = participant.name.clone();
+            Ok(entity)
+        }
+        Combatant::Improvised { level, stats } => Ok(Entity {
+            name: participant.name.clone(),
+            level: *level,
+            force: stats.force,
+            resistance: stats.resistance,
+            vitesse: stats.vitesse,
+            resistance_magique: stats.resistance_magique,
+            force_magique: stats.force_magique,
+            ..Default::default()
+        }),
+    }
+}
+
+/// Resolves the attacks declared this turn, fastest participant first.
+/// Each attack is one exchange against its target: the loser takes the
+/// wounds, and the weapon wear when they are an adventurer, and leaves the
+/// fight at death's door. Only adventurer sheets keep the wounds afterwards.
+pub fn resolve_turn(env: &Env) -> Result<Reply> {
+    let mut encounter = current(env)?;
+    let settings = &env.config.duel;
+    let repo = env.adventurers.as_ref();
+    let mut adventurers: HashMap<String, Adventurer> = HashMap::new();
+    for participant in encounter.participants.iter().filter(|p| !p.out) {
+        if participant.combatant == Combatant::Adventurer {
+            let adventurer = get_adventurer(repo, &participant.name)?;
+            adventurers.insert(participant.name.clone(), adventurer);
+        }
+    }
+
+    // Ties keep the order in which participants joined.
+    let mut notes = Vec::new();
+    let mut order = Vec::new();
+    for participant in encounter.participants.iter().filter(|p| !p.out) {
+        let entity = participant_entity(env, participant, &adventurers, &mut notes)?;
+        order.push((entity.vitesse, participant.name.clone()));
+    }
+    order.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    let names: Vec<&str> = order.iter().map(|(_, name)| name.as_str()).collect();
+    let mut log = format!("**Tour {}**: {}\n", encounter.turn, names.join(" > "));
+    for note in notes {
+        log.push_str(&format!("{}\n", note));
+    }
+
+    // Wear taken by each adventurer who lost an exchange.
+    let mut hurt: HashMap<String, f32> = HashMap::new();
+    for (_, name) in &order {
+        let participant = encounter.participant(name).expect("ordered above");
+        let target = match (&participant.target, participant.out) {
+            (Some(target), false) => target.clone(),
+            _ => continue,
+        };
+        // Effects may have been added since the attack was declared.
+        if let Some(adventurer) = adventurers.get(name) {
+            if let Err(why) = ensure_allowed(env, adventurer, Restriction::Combat) {
+                log.push_str(&format!("{} → {}: {}\n", name, target, why));
+                continue;
+            }
+        }
+        let defender = match encounter.participant(&target) {
+            Some(defender) if defender.out => {
+                log.push_str(&format!("{} → {}: déjà hors de combat\n", name, target));
+                continue;
+            }
+            Some(defender) => defender,
+            None => continue,
+        };
+
+        // Rebuilt for each attack since a weapon may break during the turn.
+        let entities = Entities {
+            entity_one: participant_entity(env, participant, &adventurers, &mut Vec::new())?,
+            entity_second: participant_entity(env, defender, &adventurers, &mut Vec::new())?,
+        };
+        let (record, result) = record_roll(env, &entities)?;
+        let loser_equipped = match result.first_won {
+            true => entities.entity_second.equipped,
+            false => entities.entity_one.equipped,
+        };
+        log.push_str(&format!(
+            "{} → {} (#{}): {} touche {}, causant {}\n",
+            name, target, record.id, result.gagnant, result.perdant, result.diff_force
+        ));
+
+        let participant = encounter
+            .participant_mut(&result.perdant)
+            .expect("both sides take part");
+        let state = match adventurers.get_mut(&result.perdant) {
+            Some(loser) => {
+                let usure = hurt.entry(result.perdant.clone()).or_default();
+                if loser_equipped {
+                    *usure += result.usure;
+                    if let Some(note) = wear_weapon(loser, result.usure) {
+                        log.push_str(&format!("{}\n", note));
+                    }
+                }
+                if let Some(state) = wound(
+                    settings,
+                    loser,
+                    &mut participant.wounds,
+                    result.diff_force,
+                    &result.gagnant,
+                ) {
+                    log.push_str(&format!("{} passe à l'état {}\n", loser.name, state));
+                }
+                loser.health.state
+            }
+            None => {
+                let before = settings.health(participant.wounds);
+                participant.wounds += result.diff_force.wounds();
+                let state = settings.health(participant.wounds);
+                if state > before {
+                    log.push_str(&format!("{} passe à l'état {}\n", participant.name, state));
+                }
+                state
+            }
+        };
+        if state >= HealthState::DeathDoor {
+            participant.out = true;
+            log.push_str(&format!("{} est hors de combat\n", participant.name));
+        }
+    }
+
+    encounter.next_turn();
+    if let Some(side) = encounter.winner() {
+        log.push_str(&format!("Le camp {} remporte la rencontre.\n", side));
+    }
+    for (name, usure) in hurt {
+        save_fight(env, &adventurers[&name], usure)?;
+    }
+    env.encounters.save(&encounter)?;
+
+    Ok(Reply::text(log))
+}
+
+pub fn end(env: &Env) -> Result<Reply> {
+    let encounter = current(env)?;
+    env.encounters.remove(encounter.channel_id)?;
+
+    Ok(Reply::text(format!(
+        "Rencontre terminée après {} tours",
+        encounter.turn - 1
+    )))
+}
+
+fn run_state(env: &Env, _: &Args) -> Result<Reply> {
+    state(env)
+}
+
+fn run_create(env: &Env, _: &Args) -> Result<Reply> {
+    create(env)
+}
+
+fn run_add(env: &Env, args: &Args) -> Result<Reply> {
+    let names: Vec<&str> = args.required("noms").split_whitespace().collect();
+    add(env, args.required("camp"), &names)
+}
+
+fn run_declare(env: &Env, args: &Args) -> Result<Reply> {
+    declare(env, args.required("attaquant"), args.required("cible"))
+}
+
+fn run_turn(env: &Env, _: &Args) -> Result<Reply> {
+    resolve_turn(env)
+}
+
+fn run_end(env: &Env, _: &Args) -> Result<Reply> {
+    end(env)
+}
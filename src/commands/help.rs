@@ -0,0 +1,91 @@
+use danjon_bot::error::Result;
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{find_command, CommandSpec, Env, Reply, COMMANDS};
+
+pub const AIDE: CommandSpec = CommandSpec {
+    name: "aide",
+    aliases: &["help"],
+    description: "Liste les commandes ou détaille l'une d'elles",
+    args: &[ArgSpec {
+        name: "commande",
+        kind: ArgKind::Text,
+        required: false,
+        description: "Commande à détailler",
+    }],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(aide),
+};
+
+fn details(res: &mut String, prefix: &str, spec: &CommandSpec, parent: Option<&CommandSpec>) {
+    res.push_str(&format!(
+        "`{}`\n{}\n",
+        spec.usage(prefix, parent),
+        spec.description
+    ));
+    for arg in spec.args {
+        res.push_str(&format!("- `{}`: {}\n", arg.name, arg.description));
+    }
+    for flag in spec.flags {
+        res.push_str(&format!("- `{}`: {}\n", flag.syntax(), flag.description));
+    }
+}
+
+pub fn command_help(prefix: &str, spec: &CommandSpec) -> Reply {
+    let mut res = String::new();
+    if spec.subcommands.is_empty() {
+        details(&mut res, prefix, spec, None);
+    } else {
+        match spec.run {
+            Some(_) => details(&mut res, prefix, spec, None),
+            None => res.push_str(&format!(
+                "`{}{}`: {}\n",
+                prefix, spec.name, spec.description
+            )),
+        }
+        for sub in spec.subcommands {
+            res.push('\n');
+            details(&mut res, prefix, sub, Some(spec));
+        }
+    }
+    if !spec.aliases.is_empty() {
+        let aliases: Vec<String> = spec
+            .aliases
+            .iter()
+            .map(|a| format!("`{}{}`", prefix, a))
+            .collect();
+        res.push_str(&format!("\nAlias: {}\n", aliases.join(", ")));
+    }
+
+    Reply::text(res)
+}
+
+fn aide(env: &Env, args: &Args) -> Result<Reply> {
+    let prefix = env.config.prefix.as_str();
+    if let Some(name) = args.get("commande") {
+        return Ok(match find_command(name.trim_start_matches(prefix)) {
+            Some(spec) => command_help(prefix, spec),
+            None => Reply::text(format!(
+                "Commande inconnue: `{}`. Tape `{}aide` pour la liste des commandes.",
+                name, prefix
+            )),
+        });
+    }
+
+    let mut res = String::from("Commandes disponibles:\n");
+    for spec in COMMANDS {
+        res.push_str(&format!(
+            "`{}{}`: {}\n",
+            prefix, spec.name, spec.description
+        ));
+    }
+    res.push_str(&format!(
+        "\nTape `{}aide <commande>` pour le détail d'une commande.",
+        prefix
+    ));
+
+    Ok(Reply::text(res))
+}
@@ -0,0 +1,162 @@
+use serenity::builder::CreateEmbed;
+
+use danjon_bot::error::{Error, Result};
+use danjon_bot::rolls::{RollRecord, Subject};
+use danjon_bot::storage::StorageError;
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::roll::{DiffStatsState, Entity, RollInputs};
+use crate::commands::{CommandSpec, Env, Reply};
+
+pub const HISTORIQUE: CommandSpec = CommandSpec {
+    name: "historique",
+    aliases: &["combats"],
+    description: "Liste les derniers rolls d'un combattant, avec son bilan",
+    args: &[
+        ArgSpec {
+            name: "nom",
+            kind: ArgKind::Text,
+            required: true,
+            description: "Aventurier ou créature",
+        },
+        ArgSpec {
+            name: "nombre",
+            kind: ArgKind::Integer,
+            required: false,
+            description: "Nombre de rolls listés, 10 par défaut",
+        },
+    ],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run),
+};
+
+const DEFAULT_LENGTH: usize = 10;
+// Keeps the list within an embed description.
+const MAX_LENGTH: usize = 25;
+
+// How often a combattant dealt or took hits of each attack tier, as tallied
+// by the storage.
+fn tier_counts(tiers: &[(String, u32)]) -> Result<[u32; DiffStatsState::ALL.len()]> {
+    let mut counts = [0; DiffStatsState::ALL.len()];
+    for (key, count) in tiers {
+        let tier: DiffStatsState = key
+            .parse()
+            .map_err(|why| StorageError::Corrupted(format!("historique: {}", why)))?;
+        let i = DiffStatsState::ALL
+            .iter()
+            .position(|t| *t == tier)
+            .expect("ALL lists every tier");
+        counts[i] += count;
+    }
+    Ok(counts)
+}
+
+// One line per tier reached, the strongest first.
+fn tiers(counts: &[u32]) -> String {
+    let lines: Vec<String> = DiffStatsState::ALL
+        .iter()
+        .zip(counts)
+        .rev()
+        .filter(|(_, count)| **count > 0)
+        .map(|(tier, count)| format!("{}: {}", tier, count))
+        .collect();
+    match lines.is_empty() {
+        true => "aucun".to_string(),
+        false => lines.join("\n"),
+    }
+}
+
+fn opponent<'a>(record: &RollRecord, inputs: &'a RollInputs, subject: &Subject) -> &'a Entity {
+    match record.is_first(subject) {
+        true => &inputs.entities.entity_second,
+        false => &inputs.entities.entity_one,
+    }
+}
+
+/// The `count` latest rolls of `name`, from `?roll`, duels and encounters
+/// alike, with the wins, losses and attack tiers of all of them.
+pub fn history(env: &Env, name: &str, count: Option<usize>) -> Result<Reply> {
+    let count = count.unwrap_or(DEFAULT_LENGTH);
+    if count == 0 || count > MAX_LENGTH {
+        return Err(Error::Validation(format!(
+            "l'historique liste de 1 à {} rolls (reçu: {})",
+            MAX_LENGTH, count
+        )));
+    }
+    // Adventurers are followed by their sheet, through renames; creatures
+    // by their name as written in the bestiary.
+    let (name, subject) = match (env.adventurers.find(name)?, env.data.bestiary.find(name)) {
+        (Some(adventurer), _) => (adventurer.name, Subject::Adventurer(adventurer.id)),
+        (None, Some(creature)) => (creature.name.clone(), Subject::Named(creature.name.clone())),
+        (None, None) => (name.to_string(), Subject::Named(name.to_string())),
+    };
+    let tally = env.rolls.tally(&subject)?;
+    if tally.wins + tally.losses == 0 {
+        return Ok(Reply::text(format!(
+            "{} n'a encore pris part à aucun roll.",
+            name
+        )));
+    }
+
+    let mut lines = Vec::new();
+    for record in env.rolls.history(&subject, count)? {
+        let inputs = RollInputs::of(&record)?;
+        let result = inputs.result(&record)?;
+        let other = opponent(&record, &inputs, &subject);
+        let weapon = other
+            .weapon
+            .as_ref()
+            .map_or(String::new(), |weapon| format!(" ({})", weapon));
+        let outcome = match record.won(&subject) {
+            true => format!("**victoire**, cause {}", result.diff_force),
+            false => format!(
+                "défaite, subit {}, usure {}",
+                result.diff_force, result.usure
+            ),
+        };
+        lines.push(format!(
+            "`#{}` <t:{}:d> contre {}{}: {} ({} en vitesse)",
+            record.id, record.created_at, other.name, weapon, outcome, result.diff_vitesse
+        ));
+    }
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!("Historique de {}", name))
+        .description(lines.join("\n"))
+        .fields(vec![
+            (
+                "Bilan",
+                format!(
+                    "{} rolls\n{} victoires\n{} défaites",
+                    tally.wins + tally.losses,
+                    tally.wins,
+                    tally.losses
+                ),
+                true,
+            ),
+            ("Coups portés", tiers(&tier_counts(&tally.dealt)?), true),
+            ("Coups reçus", tiers(&tier_counts(&tally.taken)?), true),
+        ]);
+
+    Ok(Reply {
+        embed: Some(embed),
+        ..Default::default()
+    })
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    let count = match args.get("nombre") {
+        Some(count) => Some(count.parse().map_err(|_| {
+            Error::Validation(format!(
+                "l'historique liste de 1 à {} rolls (reçu: {})",
+                MAX_LENGTH, count
+            ))
+        })?),
+        None => None,
+    };
+    history(env, args.required("nom"), count)
+}
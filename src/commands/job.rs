@@ -0,0 +1,153 @@
+use rand::{thread_rng, Rng};
+
+use danjon_bot::adventurer::{get_adventurer, Jobs, JOB_LEVEL_MAX};
+use danjon_bot::error::{Error, Result};
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{CommandSpec, Env, Reply};
+
+const NOM_METIER: ArgSpec = ArgSpec {
+    name: "metier",
+    kind: ArgKind::Text,
+    required: true,
+    description: "Métier, par exemple forgeron ou alchimiste_pharmacien",
+};
+
+pub const METIER: CommandSpec = CommandSpec {
+    name: "metier",
+    aliases: &["métier"],
+    description: "Fait un jet de métier contre une difficulté",
+    args: &[
+        ArgSpec {
+            name: "nom",
+            kind: ArgKind::Text,
+            required: true,
+            description: "Nom de l'aventurier",
+        },
+        NOM_METIER,
+        ArgSpec {
+            name: "difficulte",
+            kind: ArgKind::Text,
+            required: true,
+            description: "Difficulté du jet, par exemple facile ou difficile",
+        },
+    ],
+    flags: &[],
+    subcommands: &[CommandSpec {
+        name: "paliers",
+        aliases: &[],
+        description: "Liste ce que débloque chaque niveau d'un métier",
+        args: &[NOM_METIER],
+        flags: &[],
+        subcommands: &[],
+        default_subcommand: None,
+        game_master: false,
+        run: Some(run_unlocks),
+    }],
+    default_subcommand: Some("jet"),
+    game_master: false,
+    run: Some(run),
+};
+
+fn unknown_difficulty(env: &Env, name: &str) -> Error {
+    let known: Vec<String> = env
+        .config
+        .jobs
+        .difficulties
+        .iter()
+        .map(|(name, score)| format!("{} ({})", name, score))
+        .collect();
+    Error::Validation(format!(
+        "difficulté inconnue: {} (difficultés: {})",
+        name,
+        known.join(", ")
+    ))
+}
+
+/// Rolls the job die of `name` plus their `job` level against `difficulty`.
+/// A success earns as much job XP as the score that had to be reached, but
+/// only when a game master runs the check, so that players cannot farm XP
+/// by rolling on their own.
+pub fn check(env: &Env, name: &str, job: &str, difficulty: &str) -> Result<Reply> {
+    let settings = &env.config.jobs;
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let job = Jobs::get(job)?;
+    let (difficulty, score) = settings
+        .difficulty(difficulty)
+        .ok_or_else(|| unknown_difficulty(env, difficulty))?;
+    let label = Jobs::label(job);
+    let level = adventurer.jobs.level(job).unwrap_or_default();
+
+    let die = thread_rng().gen_range(1..=settings.die);
+    let bonus = level as u32 * settings.level_bonus;
+    let total = die + bonus;
+    let mut res = format!(
+        "{} tente un jet de {} (niveau {}), difficulté {} ({}):\n🎲 {}/{} + {} = **{}**",
+        adventurer.name, label, level, difficulty, score, die, settings.die, bonus, total
+    );
+    if total < *score {
+        res.push_str(" → **échec**");
+        return Ok(Reply::text(res));
+    }
+
+    res.push_str(" → **réussite**");
+    if !env.game_master {
+        res.push_str("\nSeul un jet lancé par un maître du jeu rapporte de l'XP de métier.");
+        return Ok(Reply::text(res));
+    }
+    res.push('\n');
+    let gained = adventurer.gain_job_xp(job, *score, settings);
+    env.adventurers.update(&adventurer)?;
+    let new_level = level + gained;
+    if gained > 0 {
+        res.push_str(&format!("**{} niveau {}!**\n", label, new_level));
+        for unlock in env.data.jobs.unlocked_between(job, level, new_level) {
+            res.push_str(&format!("Débloque: {}\n", unlock.debloque));
+        }
+    }
+    match new_level < JOB_LEVEL_MAX {
+        true => res.push_str(&format!(
+            "+{} XP de {}: {}/{} vers le niveau {}",
+            score,
+            label,
+            adventurer.job_xp.get(job).copied().unwrap_or_default(),
+            settings.to_next(new_level),
+            new_level + 1
+        )),
+        false => res.push_str(&format!("{} est au niveau maximum", label)),
+    }
+
+    Ok(Reply::text(res))
+}
+
+pub fn unlocks(env: &Env, job: &str) -> Result<Reply> {
+    let job = Jobs::get(job)?;
+    let label = Jobs::label(job);
+    let unlocks = env.data.jobs.unlocks(job);
+    if unlocks.is_empty() {
+        return Ok(Reply::text(format!(
+            "{} ne débloque rien de particulier.",
+            label
+        )));
+    }
+
+    let mut res = format!("Paliers de {}:\n", label);
+    for unlock in unlocks {
+        res.push_str(&format!("Niveau {}: {}\n", unlock.niveau, unlock.debloque));
+    }
+
+    Ok(Reply::text(res))
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    check(
+        env,
+        args.required("nom"),
+        args.required("metier"),
+        args.required("difficulte"),
+    )
+}
+
+fn run_unlocks(env: &Env, args: &Args) -> Result<Reply> {
+    unlocks(env, args.required("metier"))
+}
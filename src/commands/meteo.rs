@@ -1,52 +1,194 @@
-use serenity::{model::channel::Message, prelude::*};
-use std::env;
-use std::fs::File;
-use std::io::BufReader;
-use serde::{Deserialize, Serialize};
-
-#[derive(Serialize, Deserialize)]
-struct Meteos {
-    palliers: Vec<Pallier>
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-struct Pallier {
-    banner_url: String,
-    temperies: Vec<Climat>,
-    intemperies: Vec<Climat>
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-struct Climat {
-    name: String,
-    description: String,
-    image_url: String,
-    temperature: u8,
-    humidite: u8,
-    duree: u8,
-    protection: u8
-}
-
-pub async fn meteo(ctx: Context, msg: Message) {
-    let path = env::var("METEO_JSON").expect("Error in the env variable");
-    let file = File::open(path);
-    let reader = BufReader::new(file.unwrap());
-    let res: Meteos = serde_json::from_reader(reader).unwrap();
-
-    let msg = msg.channel_id.send_message(&ctx.http, |m| {
-        m.add_file(res.palliers.last().unwrap().banner_url.as_str())
-            .embed(|e| {
-                e.title(res.palliers.last().unwrap().temperies.last().unwrap().name.clone())
-                    .description("L'humidité dans l'air s'intensifie pour laisser paraître les couleurs multicolorées d'un ar-en-ciel! Le beau temps avec un peu de nébulosité et une température douce.")
-                    .image("https://cdn.discordapp.com/attachments/890628271157411891/892428906542555136/arc-en-ciel.gif")
-                    .fields(vec![
-                        ("__Température__", "19°C", true),
-                        ("__Humidité__", "40%", true),
-                        ("\u{200B}", "\u{200B}", false),
-                        ("__Durée__", "1 semaine", true),
-                        ("__Protection contre les intempéries__", "O semaine", true)
-                    ])
-            })
-    }).await;
-    if let Err(why) = msg {println!("Error sending meteo embed: {:?}", why);}
+use rand::thread_rng;
+use serenity::builder::CreateEmbed;
+use serenity::prelude::*;
+use std::time::Duration;
+use tracing::error;
+
+use danjon_bot::config;
+use danjon_bot::data;
+use danjon_bot::error::{Error, Result};
+use danjon_bot::storage;
+use danjon_bot::weather::{rotate, Climat, Meteos, WeatherEntry};
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{now, send, CommandSpec, Env, Reply};
+
+const PALIER: ArgSpec = ArgSpec {
+    name: "palier",
+    kind: ArgKind::Integer,
+    required: false,
+    description: "Numéro du palier, le plus haut par défaut",
+};
+
+pub const METEO: CommandSpec = CommandSpec {
+    name: "meteo",
+    aliases: &["météo"],
+    description: "Affiche la météo actuelle d'un palier",
+    args: &[PALIER],
+    flags: &[],
+    subcommands: &[CommandSpec {
+        name: "historique",
+        aliases: &[],
+        description: "Liste les dernières météos d'un palier",
+        args: &[PALIER],
+        flags: &[],
+        subcommands: &[],
+        default_subcommand: None,
+        game_master: false,
+        run: Some(run_history),
+    }],
+    default_subcommand: Some("actuelle"),
+    game_master: false,
+    run: Some(run),
+};
+
+const HISTORY_LENGTH: usize = 10;
+
+// How often the calendar looks for weathers that are over.
+const ROTATION_PERIOD: Duration = Duration::from_secs(60);
+
+fn weeks(count: u8) -> String {
+    match count {
+        0 | 1 => format!("{} semaine", count),
+        _ => format!("{} semaines", count),
+    }
+}
+
+pub fn climat_embed(number: usize, climat: &Climat, entry: &WeatherEntry) -> CreateEmbed {
+    let mut embed = CreateEmbed::default();
+    embed
+        .title(format!("Palier {}: {}", number, climat.name))
+        .description(&climat.description)
+        .image(&climat.image_url)
+        .fields(vec![
+            ("__Température__", format!("{}°C", climat.temperature), true),
+            ("__Humidité__", format!("{}%", climat.humidite), true),
+            ("\u{200B}", "\u{200B}".to_string(), false),
+            ("__Durée__", weeks(climat.duree), true),
+            (
+                "__Protection contre les intempéries__",
+                weeks(climat.protection),
+                true,
+            ),
+            ("__Jusqu'au__", format!("<t:{}:f>", entry.ends_at), false),
+        ]);
+
+    embed
+}
+
+// Palier `number`, the highest palier by default.
+fn pallier_number(meteos: &Meteos, number: Option<usize>) -> Result<usize> {
+    let number = number.unwrap_or(meteos.palliers.len());
+    match meteos.pallier(number) {
+        Some(_) => Ok(number),
+        None => Err(Error::Validation(format!(
+            "le palier {} n'existe pas (1 à {})",
+            number,
+            meteos.palliers.len()
+        ))),
+    }
+}
+
+/// The current weather of a palier. Weathers are only drawn by the calendar
+/// so that every change is announced.
+pub fn weather(env: &Env, number: Option<usize>) -> Result<Reply> {
+    let meteos = &env.data.meteos;
+    let number = pallier_number(meteos, number)?;
+    let pallier = meteos.pallier(number).expect("checked above");
+    let current = env.weather.current(number as u32)?.and_then(|entry| {
+        let climat = pallier.climat(entry.kind, &entry.climat)?;
+        Some((entry, climat))
+    });
+
+    Ok(match current {
+        Some((entry, climat)) => Reply {
+            embed: Some(climat_embed(number, climat, &entry)),
+            file: Some(pallier.banner_url.clone()),
+            ..Default::default()
+        },
+        None => Reply::text(format!(
+            "La météo du palier {} n'a pas encore été tirée, réessaie dans une minute.",
+            number
+        )),
+    })
+}
+
+pub fn weather_history(env: &Env, number: Option<usize>) -> Result<Reply> {
+    let number = pallier_number(&env.data.meteos, number)?;
+    let history = env.weather.history(number as u32, HISTORY_LENGTH)?;
+    if history.is_empty() {
+        return Ok(Reply::text(format!(
+            "Le palier {} n'a encore connu aucune météo.",
+            number
+        )));
+    }
+
+    let mut res = format!("Dernières météos du palier {}:\n", number);
+    for entry in history {
+        res.push_str(&format!(
+            "<t:{}:d> → <t:{}:d>: **{}** ({})\n",
+            entry.started_at, entry.ends_at, entry.climat, entry.kind
+        ));
+    }
+
+    Ok(Reply::text(res))
+}
+
+/// Draws a new weather for every palier whose weather is over and posts it
+/// to the announcement channel.
+async fn rotate_all(ctx: &Context) {
+    let config = config::config(ctx).await;
+    let game_data = data::game_data(ctx).await.get();
+    let calendar = storage::weather(ctx).await;
+
+    let mut changes = Vec::new();
+    for number in 1..=game_data.meteos.palliers.len() {
+        let forecast = rotate(
+            calendar.as_ref(),
+            &game_data.meteos,
+            number,
+            &config.weather,
+            now(),
+            &mut thread_rng(),
+        );
+        match forecast {
+            Ok(Some(forecast)) if forecast.changed => {
+                let pallier = game_data.meteos.pallier(number).expect("rotated above");
+                changes.push(Reply {
+                    embed: Some(climat_embed(number, forecast.climat, &forecast.entry)),
+                    file: Some(pallier.banner_url.clone()),
+                    ..Default::default()
+                });
+            }
+            Ok(_) => {}
+            Err(why) => error!("Error rotating the weather of palier {}: {:?}", number, why),
+        }
+    }
+
+    if let Some(channel_id) = config.weather.announcement_channel {
+        for reply in changes {
+            send(ctx, channel_id, reply).await;
+        }
+    }
+}
+
+pub async fn run_calendar(ctx: Context) {
+    let mut interval = tokio::time::interval(ROTATION_PERIOD);
+    loop {
+        interval.tick().await;
+        rotate_all(&ctx).await;
+    }
+}
+
+fn palier(args: &Args) -> Option<usize> {
+    // Checked as an integer by the parser; a negative number is no palier.
+    args.get("palier").map(|n| n.parse().unwrap_or(0))
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    weather(env, palier(args))
+}
+
+fn run_history(env: &Env, args: &Args) -> Result<Reply> {
+    weather_history(env, palier(args))
 }
@@ -1,4 +1,341 @@
+use serenity::{
+    builder::CreateEmbed,
+    model::{
+        application::interaction::{
+            application_command::ApplicationCommandInteraction, InteractionResponseType,
+        },
+        channel::Message,
+        id::{ChannelId, RoleId, UserId},
+    },
+    prelude::*,
+    utils::Colour,
+};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::error;
+
+use danjon_bot::config::{self, Config};
+use danjon_bot::data::{self, GameData, GameDataCache};
+use danjon_bot::error::{Error, Result};
+use danjon_bot::storage::{
+    self, AdventurerRepository, EncounterRepository, RollRepository, WeatherRepository,
+};
+
+use args::{tokenize, usage, ArgSpec, Args, FlagSpec};
+
+pub mod admin;
 pub mod adventurer;
+pub mod args;
+pub mod bestiary;
+pub mod duel;
+pub mod effect;
+pub mod encounter;
+pub mod help;
+pub mod history;
+pub mod job;
+pub mod meteo;
 pub mod ping;
+pub mod rank;
 pub mod roll;
-pub mod meteo;
+pub mod slash;
+pub mod weapon;
+pub mod xp;
+
+/// Shared state a command may need, fetched from the client data once per
+/// invocation so that handlers can stay synchronous.
+pub struct Env {
+    pub config: Arc<Config>,
+    /// Where the command was run.
+    pub channel_id: ChannelId,
+    /// Who ran it.
+    pub user_id: UserId,
+    /// Whether the member running the command holds a game master role.
+    pub game_master: bool,
+    pub adventurers: Arc<dyn AdventurerRepository>,
+    pub weather: Arc<dyn WeatherRepository>,
+    pub encounters: Arc<dyn EncounterRepository>,
+    pub rolls: Arc<dyn RollRepository>,
+    /// Game data as it was when the command started.
+    pub data: Arc<GameData>,
+    pub game_data: Arc<GameDataCache>,
+}
+
+impl Env {
+    pub async fn load(
+        ctx: &Context,
+        channel_id: ChannelId,
+        user_id: UserId,
+        roles: &[RoleId],
+    ) -> Self {
+        let config = config::config(ctx).await;
+        let game_data = data::game_data(ctx).await;
+        Env {
+            game_master: config.is_game_master(roles),
+            config,
+            channel_id,
+            user_id,
+            adventurers: storage::adventurers(ctx).await,
+            weather: storage::weather(ctx).await,
+            encounters: storage::encounters(ctx).await,
+            rolls: storage::rolls(ctx).await,
+            data: game_data.get(),
+            game_data,
+        }
+    }
+}
+
+/// Unix time in seconds, as stored by the weather calendar and the rolls.
+pub fn now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Keeps the last lines of `log` that fit in `max` characters, noting how
+/// many were left out. Discord refuses messages over its length limits.
+pub fn tail_lines(log: &str, max: usize) -> String {
+    let lines: Vec<&str> = log.lines().collect();
+    let mut kept = 0;
+    let mut length = 0;
+    for line in lines.iter().rev() {
+        // Room for the note on the lines left out.
+        if length + line.chars().count() + 1 > max.saturating_sub(40) {
+            break;
+        }
+        length += line.chars().count() + 1;
+        kept += 1;
+    }
+    let tail = lines[lines.len() - kept..].join("\n");
+    match kept == lines.len() {
+        true => tail,
+        false => format!("… {} lignes omises\n{}", lines.len() - kept, tail),
+    }
+}
+
+pub type Handler = fn(&Env, &Args) -> Result<Reply>;
+
+pub struct CommandSpec {
+    pub name: &'static str,
+    pub aliases: &'static [&'static str],
+    pub description: &'static str,
+    pub args: &'static [ArgSpec],
+    pub flags: &'static [FlagSpec],
+    /// When no subcommand matches, the command's own handler runs if it has
+    /// one; otherwise `?aide` is shown for it.
+    pub subcommands: &'static [CommandSpec],
+    /// Name under which the command's own handler is also reached next to
+    /// its subcommands, as in `/xp voir`: a slash command with subcommands
+    /// cannot take options of its own.
+    pub default_subcommand: Option<&'static str>,
+    /// Reserved to the roles listed in `commands.game_master_roles`; for a
+    /// command with subcommands this covers all of them.
+    pub game_master: bool,
+    pub run: Option<Handler>,
+}
+
+impl CommandSpec {
+    pub fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    }
+
+    pub fn is_default_subcommand(&self, name: &str) -> bool {
+        self.default_subcommand
+            .is_some_and(|default| default.eq_ignore_ascii_case(name))
+    }
+
+    pub fn usage(&self, prefix: &str, parent: Option<&CommandSpec>) -> String {
+        let path = match parent {
+            Some(parent) => format!("{}{} {}", prefix, parent.name, self.name),
+            None => format!("{}{}", prefix, self.name),
+        };
+        usage(&path, self.args, self.flags)
+    }
+}
+
+pub static COMMANDS: &[CommandSpec] = &[
+    ping::PING,
+    meteo::METEO,
+    adventurer::STATS,
+    roll::ROLL,
+    roll::CHANCES,
+    roll::REJOUER,
+    history::HISTORIQUE,
+    roll::SORT,
+    duel::DUEL,
+    encounter::RENCONTRE,
+    bestiary::BESTIAIRE,
+    adventurer::AVENTURIER,
+    effect::EFFET,
+    xp::XP,
+    rank::RANG,
+    job::METIER,
+    weapon::ARME,
+    admin::VERIFIER,
+    admin::RECHARGER,
+    help::AIDE,
+];
+
+pub fn find_command(name: &str) -> Option<&'static CommandSpec> {
+    COMMANDS.iter().find(|c| c.matches(name))
+}
+
+/// The answer given instead of running a game master command.
+pub fn game_master_only() -> Reply {
+    Reply::error(
+        "Commande réservée",
+        "Cette commande est réservée aux maîtres du jeu.",
+    )
+}
+
+fn run_command(env: &Env, tokens: &[String]) -> Option<Result<Reply>> {
+    let prefix = env.config.prefix.as_str();
+    let name = tokens[0].strip_prefix(prefix)?;
+    let spec = match find_command(name) {
+        Some(spec) => spec,
+        // Plain punctuation such as "??" is chat, not a mistyped command.
+        None if !name.is_empty() && name.chars().all(char::is_alphanumeric) => {
+            return Some(Ok(Reply::text(format!(
+                "Commande inconnue: `{}{}`. Tape `{}aide` pour la liste des commandes.",
+                prefix, name, prefix
+            ))));
+        }
+        None => return None,
+    };
+    let (parent, spec, rest) = if spec.subcommands.is_empty() {
+        (None, spec, &tokens[1..])
+    } else {
+        let sub = tokens.get(1).map(String::as_str).unwrap_or_default();
+        match spec.subcommands.iter().find(|s| s.matches(sub)) {
+            Some(sub) => (Some(spec), sub, &tokens[2..]),
+            None if spec.is_default_subcommand(sub) => (None, spec, &tokens[2..]),
+            None if spec.run.is_some() => (None, spec, &tokens[1..]),
+            None => return Some(Ok(help::command_help(prefix, spec))),
+        }
+    };
+    if (spec.game_master || parent.is_some_and(|p| p.game_master)) && !env.game_master {
+        return Some(Ok(game_master_only()));
+    }
+    let run = spec
+        .run
+        .expect("commands without subcommands have a handler");
+
+    Some(match args::parse(rest, spec.args, spec.flags) {
+        Ok(args) => run(env, &args),
+        Err(why) => Ok(Reply::error(
+            "Utilisation incorrecte",
+            format!("{}\n`{}`", why, spec.usage(prefix, parent)),
+        )),
+    })
+}
+
+pub async fn dispatch(ctx: &Context, msg: &Message) {
+    let config = config::config(ctx).await;
+    let tokens = tokenize(&msg.content);
+    let is_command = tokens
+        .first()
+        .is_some_and(|t| t.starts_with(&config.prefix));
+    if !is_command || !config.allows(msg.guild_id, msg.channel_id) {
+        return;
+    }
+    let roles = msg
+        .member
+        .as_ref()
+        .map(|member| member.roles.as_slice())
+        .unwrap_or_default();
+    let env = Env::load(ctx, msg.channel_id, msg.author.id, roles).await;
+    let reply = match run_command(&env, &tokens) {
+        Some(Ok(reply)) => reply,
+        Some(Err(why)) => {
+            error!(
+                "Error running {:?} for {} in {}: {:?}",
+                msg.content,
+                msg.author.tag(),
+                msg.channel_id,
+                why
+            );
+            Reply::from(why)
+        }
+        None => return,
+    };
+    send(ctx, msg.channel_id, reply).await;
+}
+
+/// What a command answers, independently of whether it was invoked through
+/// a `?` prefix message or a slash command.
+#[derive(Default)]
+pub struct Reply {
+    pub content: Option<String>,
+    pub embed: Option<CreateEmbed>,
+    pub file: Option<String>,
+}
+
+impl Reply {
+    pub fn text(content: impl ToString) -> Self {
+        Reply {
+            content: Some(content.to_string()),
+            ..Default::default()
+        }
+    }
+
+    pub fn error(title: &str, description: impl ToString) -> Self {
+        let mut embed = CreateEmbed::default();
+        embed
+            .title(title)
+            .description(description)
+            .colour(Colour::RED);
+        Reply {
+            embed: Some(embed),
+            ..Default::default()
+        }
+    }
+}
+
+impl From<Error> for Reply {
+    fn from(why: Error) -> Self {
+        Reply::error(why.title(), &why)
+    }
+}
+
+pub async fn send(ctx: &Context, channel_id: ChannelId, reply: Reply) {
+    let msg = channel_id
+        .send_message(&ctx.http, |m| {
+            if let Some(content) = &reply.content {
+                m.content(content);
+            }
+            if let Some(embed) = reply.embed.clone() {
+                m.set_embed(embed);
+            }
+            if let Some(file) = &reply.file {
+                m.add_file(file.as_str());
+            }
+            m
+        })
+        .await;
+    if let Err(why) = msg {
+        error!("Error sending message: {:?}", why);
+    }
+}
+
+pub async fn respond(ctx: &Context, command: &ApplicationCommandInteraction, reply: Reply) {
+    let response = command
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    if let Some(content) = &reply.content {
+                        d.content(content);
+                    }
+                    if let Some(embed) = reply.embed.clone() {
+                        d.set_embed(embed);
+                    }
+                    if let Some(file) = &reply.file {
+                        d.add_file(file.as_str());
+                    }
+                    d
+                })
+        })
+        .await;
+    if let Err(why) = response {
+        error!("Error responding to /{}: {:?}", command.data.name, why);
+    }
+}
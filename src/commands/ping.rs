@@ -1,7 +1,24 @@
-use serenity::{model::channel::Message, prelude::*};
+use danjon_bot::error::Result;
 
-pub async fn run(ctx: Context, msg: Message) {
-    if let Err(why) = msg.channel_id.say(&ctx.http, "Pong!").await {
-        println!("Error sending message: {:?}", why);
-    }
+use crate::commands::args::Args;
+use crate::commands::{CommandSpec, Env, Reply};
+
+pub const PING: CommandSpec = CommandSpec {
+    name: "ping",
+    aliases: &[],
+    description: "Vérifie que le bot répond",
+    args: &[],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run),
+};
+
+pub fn ping() -> Reply {
+    Reply::text("Pong!")
+}
+
+fn run(_: &Env, _: &Args) -> Result<Reply> {
+    Ok(ping())
 }
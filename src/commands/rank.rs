@@ -0,0 +1,148 @@
+use danjon_bot::adventurer::{get_adventurer, Adventurer};
+use danjon_bot::error::{Error, Result};
+use danjon_bot::ranks::{Promotion, RankRequirement};
+
+use crate::commands::adventurer::sheet_of;
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{now, CommandSpec, Env, Reply};
+
+const NOM: ArgSpec = ArgSpec {
+    name: "nom",
+    kind: ArgKind::Text,
+    required: true,
+    description: "Nom de l'aventurier",
+};
+
+pub const RANG: CommandSpec = CommandSpec {
+    name: "rang",
+    aliases: &["rank"],
+    description: "Affiche le rang d'un aventurier et ce qu'il lui manque pour le suivant",
+    args: &[NOM],
+    flags: &[],
+    subcommands: &[CommandSpec {
+        name: "promouvoir",
+        aliases: &["promotion"],
+        description: "Fait passer un aventurier au rang suivant s'il en remplit les conditions",
+        args: &[NOM],
+        flags: &[],
+        subcommands: &[],
+        // The member running it is recorded as having granted it.
+        default_subcommand: None,
+        game_master: true,
+        run: Some(run_promote),
+    }],
+    default_subcommand: Some("voir"),
+    game_master: false,
+    run: Some(run),
+};
+
+fn mark(met: bool) -> &'static str {
+    match met {
+        true => "✅",
+        false => "❌",
+    }
+}
+
+// What is still missing for `requirement`.
+fn missing(adventurer: &Adventurer, requirement: &RankRequirement) -> Vec<String> {
+    requirement
+        .progress(adventurer)
+        .into_iter()
+        .filter(|(met, _)| !met)
+        .map(|(_, line)| line)
+        .collect()
+}
+
+pub fn rank(env: &Env, name: &str) -> Result<Reply> {
+    let adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let mut res = format!(
+        "{} est rang {} (niveau maximum: {}).\n",
+        adventurer.name,
+        adventurer.rank,
+        env.config.xp.level_cap(adventurer.rank)
+    );
+    match env.data.ranks.next(adventurer.rank) {
+        Some(requirement) => {
+            res.push_str(&format!("Pour passer rang {}:\n", requirement.rang));
+            for (met, line) in requirement.progress(&adventurer) {
+                res.push_str(&format!("{} {}\n", mark(met), line));
+            }
+            if requirement.approbation {
+                res.push_str("Promotion laissée à l'appréciation d'un maître du jeu\n");
+            }
+        }
+        None if adventurer.rank.next().is_some() => {
+            res.push_str("Aucune condition de promotion n'est configurée.\n")
+        }
+        None => res.push_str("C'est le rang le plus haut.\n"),
+    }
+    if !adventurer.promotions.is_empty() {
+        res.push_str("Promotions:\n");
+        for promotion in &adventurer.promotions {
+            res.push_str(&format!(
+                "{} → {} <t:{}:d>, accordée par <@{}>\n",
+                promotion.from, promotion.to, promotion.promoted_at, promotion.approved_by
+            ));
+        }
+    }
+
+    Ok(Reply::text(res))
+}
+
+/// Moves `name` up one rank if they meet its requirements, on behalf of
+/// the game master running it. XP kept while their former rank capped
+/// their level is spent right away.
+pub fn promote(env: &Env, name: &str) -> Result<Reply> {
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let requirement = env.data.ranks.next(adventurer.rank).ok_or_else(|| {
+        Error::Validation(match adventurer.rank.next() {
+            Some(_) => "aucune condition de promotion n'est configurée".to_string(),
+            None => format!(
+                "{} est déjà rang {}, le plus haut",
+                adventurer.name, adventurer.rank
+            ),
+        })
+    })?;
+    let missing = missing(&adventurer, requirement);
+    if !missing.is_empty() {
+        return Err(Error::Validation(format!(
+            "{} ne peut pas encore passer rang {}, il lui manque: {}",
+            adventurer.name,
+            requirement.rang,
+            missing.join(", ")
+        )));
+    }
+
+    adventurer.promotions.push(Promotion {
+        from: adventurer.rank,
+        to: requirement.rang,
+        approved_by: env.user_id.0,
+        promoted_at: now(),
+    });
+    adventurer.rank = requirement.rang;
+    let before = adventurer.level;
+    let gained = adventurer.level_up(&env.config.xp);
+    env.adventurers.update(&adventurer)?;
+
+    let mut res = format!(
+        "**{} est promu rang {}!** (accordé par <@{}>)\n",
+        adventurer.name, adventurer.rank, env.user_id
+    );
+    if gained > 0 {
+        res.push_str(&format!(
+            "L'XP en réserve le fait passer du niveau {} au niveau {}.\n",
+            before, adventurer.level
+        ));
+    }
+    res.push_str(&sheet_of(env, &adventurer)?);
+
+    Ok(Reply::text(res))
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    rank(env, args.required("nom"))
+}
+
+fn run_promote(env: &Env, args: &Args) -> Result<Reply> {
+    promote(env, args.required("nom"))
+}
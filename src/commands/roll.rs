@@ -1,18 +1,177 @@
 use rand::thread_rng;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
-use serenity::{model::channel::Message, prelude::*};
-use std::env;
 use std::fmt;
-use std::fs::File;
-use std::io::BufReader;
-use std::vec::Vec;
+use std::str::FromStr;
+use tracing::debug;
 
-use danjon_bot::stats::{calc_stats, get_race_stats};
+use danjon_bot::adventurer::{get_adventurer, Adventurer, Magic, LEVEL_MAX};
+use danjon_bot::bestiary::Creature;
+use danjon_bot::effects::Restriction;
+use danjon_bot::error::{Error, Result};
+use danjon_bot::modifiers::{self, Modifier};
+use danjon_bot::names;
+use danjon_bot::rolls::{seeded, RollOutcome, RollRecord};
+use danjon_bot::stats::{calc_stats, DiffStats};
+use danjon_bot::storage::StorageError;
+use danjon_bot::weapons::{Weapon, Wear};
 
-use crate::commands::adventurer::{get_adventurer, Adventurer};
+use crate::commands::args::{ArgKind, ArgSpec, Args, FlagSpec};
+use crate::commands::{now, CommandSpec, Env, Reply};
 
-enum DiffStatsState {
+// Improvised fighters are described by these, written right after their
+// name as in `?roll Arthur vs "Gobelin" niv=3 for=6.5`.
+pub const STAT_FLAGS: [&str; 6] = ["niv=", "for=", "res=", "vit=", "fmag=", "rmag="];
+
+// The fighters of `?roll` and `?chances`.
+const FIGHTER_ARGS: &[ArgSpec] = &[
+    ArgSpec {
+        name: "attaquant",
+        kind: ArgKind::Text,
+        required: true,
+        description: "Premier aventurier ou créature du bestiaire",
+    },
+    ArgSpec {
+        name: "vs",
+        kind: ArgKind::Keyword,
+        required: false,
+        description: "Facultatif, sépare les deux combattants",
+    },
+    ArgSpec {
+        name: "defenseur",
+        kind: ArgKind::Text,
+        required: true,
+        description: "Second aventurier ou créature du bestiaire",
+    },
+];
+
+const FIGHTER_FLAGS: &[FlagSpec] = &[
+    FlagSpec {
+        name: "-weapon",
+        value: "arme",
+        description:
+            "Arme du catalogue du combattant qui précède, au lieu de la sienne et sans usure",
+        scoped: true,
+    },
+    FlagSpec {
+        name: "niv=",
+        value: "niveau",
+        description: "Improvise le combattant qui précède, de ce niveau (1 par défaut)",
+        scoped: true,
+    },
+    FlagSpec {
+        name: "for=",
+        value: "force",
+        description: "Force du combattant improvisé (5 par défaut)",
+        scoped: true,
+    },
+    FlagSpec {
+        name: "res=",
+        value: "résistance",
+        description: "Résistance du combattant improvisé (5 par défaut)",
+        scoped: true,
+    },
+    FlagSpec {
+        name: "vit=",
+        value: "vitesse",
+        description: "Vitesse du combattant improvisé (5 par défaut)",
+        scoped: true,
+    },
+    FlagSpec {
+        name: "fmag=",
+        value: "force magique",
+        description: "Force magique du combattant improvisé (5 par défaut)",
+        scoped: true,
+    },
+    FlagSpec {
+        name: "rmag=",
+        value: "résistance magique",
+        description: "Résistance magique du combattant improvisé (5 par défaut)",
+        scoped: true,
+    },
+];
+
+pub const ROLL: CommandSpec = CommandSpec {
+    name: "roll",
+    aliases: &["attaque"],
+    description: "Résout un échange entre deux aventuriers ou créatures",
+    args: FIGHTER_ARGS,
+    flags: FIGHTER_FLAGS,
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run),
+};
+
+pub const CHANCES: CommandSpec = CommandSpec {
+    name: "chances",
+    aliases: &["probabilites", "probabilités"],
+    description: "Calcule les chances exactes de chaque issue d'un roll, sans le lancer",
+    args: FIGHTER_ARGS,
+    flags: FIGHTER_FLAGS,
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run_chances),
+};
+
+pub const REJOUER: CommandSpec = CommandSpec {
+    name: "rejouer",
+    aliases: &["replay"],
+    description: "Rejoue un roll enregistré étape par étape, avec sa graine",
+    args: &[ArgSpec {
+        name: "id",
+        kind: ArgKind::Text,
+        required: true,
+        description: "Numéro du roll, tel qu'affiché sous son résultat (#42 ou 42)",
+    }],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run_replay),
+};
+
+pub const SORT: CommandSpec = CommandSpec {
+    name: "sort",
+    aliases: &["magie"],
+    description: "Résout un sort lancé par un aventurier sur un autre",
+    args: &[
+        ArgSpec {
+            name: "lanceur",
+            kind: ArgKind::Text,
+            required: true,
+            description: "Aventurier qui lance le sort",
+        },
+        ArgSpec {
+            name: "cible",
+            kind: ArgKind::Text,
+            required: true,
+            description: "Aventurier visé",
+        },
+        ArgSpec {
+            name: "ecole",
+            kind: ArgKind::Text,
+            required: true,
+            description: "École de magie du sort, dont l'énergie est dépensée",
+        },
+        ArgSpec {
+            name: "cout",
+            kind: ArgKind::Integer,
+            required: false,
+            description: "Énergie dépensée, 1 par défaut",
+        },
+    ],
+    flags: &[],
+    subcommands: &[],
+    default_subcommand: None,
+    game_master: false,
+    run: Some(run_sort),
+};
+
+// Ordered from the weakest hit to the strongest.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub enum DiffStatsState {
     SousDomination,
     Souspuissance,
     SousEfficace,
@@ -44,21 +203,77 @@ impl fmt::Display for DiffStatsState {
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-struct DiffStats {
-    faveur: f32,
-    avantage: f32,
-    efficace: f32,
-    surpuissance: f32,
-    domination: f32,
+impl DiffStatsState {
+    pub const ALL: [DiffStatsState; 11] = [
+        DiffStatsState::SousDomination,
+        DiffStatsState::Souspuissance,
+        DiffStatsState::SousEfficace,
+        DiffStatsState::SousAvantage,
+        DiffStatsState::SousFaveur,
+        DiffStatsState::Neutre,
+        DiffStatsState::Faveur,
+        DiffStatsState::Avantage,
+        DiffStatsState::Efficace,
+        DiffStatsState::Surpuissance,
+        DiffStatsState::Domination,
+    ];
+
+    /// The name under which recorded rolls store the tier.
+    pub fn key(&self) -> &'static str {
+        match self {
+            DiffStatsState::SousDomination => "SousDomination",
+            DiffStatsState::Souspuissance => "Souspuissance",
+            DiffStatsState::SousEfficace => "SousEfficace",
+            DiffStatsState::SousAvantage => "SousAvantage",
+            DiffStatsState::SousFaveur => "SousFaveur",
+            DiffStatsState::Neutre => "Neutre",
+            DiffStatsState::Faveur => "Faveur",
+            DiffStatsState::Avantage => "Avantage",
+            DiffStatsState::Efficace => "Efficace",
+            DiffStatsState::Surpuissance => "Surpuissance",
+            DiffStatsState::Domination => "Domination",
+        }
+    }
+
+    /// Wound points the loser of an exchange takes from a hit of this tier.
+    pub fn wounds(&self) -> u32 {
+        match self {
+            DiffStatsState::SousDomination | DiffStatsState::Souspuissance => 0,
+            DiffStatsState::SousEfficace
+            | DiffStatsState::SousAvantage
+            | DiffStatsState::SousFaveur => 1,
+            DiffStatsState::Neutre => 2,
+            DiffStatsState::Faveur => 3,
+            DiffStatsState::Avantage => 4,
+            DiffStatsState::Efficace => 5,
+            DiffStatsState::Surpuissance => 6,
+            DiffStatsState::Domination => 8,
+        }
+    }
 }
 
-struct AttackResult {
-    gagnant: String,
-    perdant: String,
-    diff_vitesse: DiffStatsState,
-    diff_force: DiffStatsState,
-    usure: f32,
+impl FromStr for DiffStatsState {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        DiffStatsState::ALL
+            .into_iter()
+            .find(|tier| tier.key() == s)
+            .ok_or_else(|| format!("palier inconnu: {}", s))
+    }
+}
+
+pub struct AttackResult {
+    pub gagnant: String,
+    pub perdant: String,
+    /// Whether the first entity won, which the names cannot tell when both
+    /// entities share one.
+    pub first_won: bool,
+    pub diff_vitesse: DiffStatsState,
+    pub diff_force: DiffStatsState,
+    pub usure: f32,
+    /// Face shown by the speed die, when the gap left it to chance.
+    pub die: Option<u32>,
 }
 
 impl fmt::Display for AttackResult {
@@ -71,30 +286,46 @@ impl fmt::Display for AttackResult {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Entities {
-    entity_one: Entity,
-    entity_second: Entity,
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entities {
+    pub entity_one: Entity,
+    pub entity_second: Entity,
 }
 
-impl Default for Entities {
-    fn default() -> Self {
-        Entities {
-            entity_one: Default::default(),
-            entity_second: Default::default(),
-        }
-    }
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entity {
+    pub name: String,
+    pub level: u8,
+    pub force: f32,
+    pub resistance: f32,
+    pub vitesse: f32,
+    pub resistance_magique: f32,
+    pub force_magique: f32,
+    /// Fights with the weapon on their sheet, which then takes the wear.
+    pub equipped: bool,
+    /// Weapon whose multipliers were applied to the stats above.
+    #[serde(default)]
+    pub weapon: Option<String>,
+    /// Id of the adventurer sheet the entity was built from, none for
+    /// creatures and improvised fighters.
+    #[serde(default)]
+    pub adventurer_id: Option<u32>,
 }
 
-#[derive(Debug, Clone)]
-struct Entity {
-    name: String,
-    level: u8,
-    force: f32,
-    resistance: f32,
-    vitesse: f32,
-    resistance_magique: f32,
-    force_magique: f32,
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} niv. {}: {:.2} for, {:.2} res, {:.2} vit, {:.2} f.mag, {:.2} r.mag",
+            self.name,
+            self.level,
+            self.force,
+            self.resistance,
+            self.vitesse,
+            self.force_magique,
+            self.resistance_magique
+        )
+    }
 }
 
 impl Default for Entity {
@@ -107,381 +338,1042 @@ impl Default for Entity {
             vitesse: 5.0,
             resistance_magique: 5.0,
             force_magique: 5.0,
+            equipped: false,
+            weapon: None,
+            adventurer_id: None,
         }
     }
 }
 
-fn set_entity_stats(name: String) -> Entity {
-    let adventurer: Adventurer = get_adventurer(name).unwrap();
+fn set_entity_stats(env: &Env, name: &str, notes: &mut Vec<String>) -> Result<Entity> {
+    let adventurer: Adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let modifiers = adventurer.modifiers(&env.data, &env.config.duel, notes);
+    adventurer_entity(env, &adventurer, &modifiers)
+}
+
+fn adventurer_entity(env: &Env, adventurer: &Adventurer, modifiers: &[Modifier]) -> Result<Entity> {
     let stats = calc_stats(
         adventurer.iv,
         adventurer.level,
-        get_race_stats(format!("{}", adventurer.race)),
-        None,
+        env.data.races.get(adventurer.race.combat_form())?,
+        modifiers::total(modifiers),
     );
     let result: Entity = Entity {
-        name: adventurer.name,
+        name: adventurer.name.clone(),
         level: adventurer.level,
         force: stats.force,
         resistance: stats.resistance,
         vitesse: stats.vitesse,
         resistance_magique: stats.resistance_magique,
         force_magique: stats.force_magique,
-        ..Default::default()
+        equipped: false,
+        weapon: None,
+        adventurer_id: Some(adventurer.id),
     };
 
-    result
+    Ok(result)
 }
 
-fn search_entities(command: Vec<String>) -> Entities {
-    let mut result: Entities = Entities::default();
-    let mut nb = 1;
+// Stats move by quarters, so a weapon's multiplier rounds up to the next one.
+// A stat the weapon leaves alone keeps its exact value, as when unarmed.
+fn scale(stat: f32, multiplier: f32) -> f32 {
+    match multiplier == 1. {
+        true => stat,
+        false => (stat * multiplier * 4.).ceil() / 4.,
+    }
+}
 
-    result.entity_one = set_entity_stats(command[nb].clone());
-    println!("{:?}", result.entity_one);
-    for (i, j) in command.clone().iter().enumerate() {
-        if i > nb {
-            if get_adventurer(j.to_string()).is_some() {
-                nb = i;
-                break;
-            }
-            match j.as_str() {
-                "-weapon" => match command[i + 1].as_str() {
-                    "Leger" => {
-                        result.entity_one.force *= 0.9; //res.force + (0.25 - res.force % 0.25)
-                        result.entity_one.force += 0.25 - result.entity_one.force % 0.25;
-                        result.entity_one.vitesse *= 1.05;
-                    }
-                    "Moyen" => {}
-                    "Lourd" => {
-                        result.entity_one.force *= 1.1;
-                        result.entity_one.vitesse *= 0.9;
-                    }
-                    _ => {
-                        result.entity_one.force *= 0.85;
-                        result.entity_one.vitesse *= 1.075;
-                    }
-                },
-                _ => {}
-            }
+fn apply_weapon(entity: &mut Entity, weapon: &Weapon) {
+    entity.force = scale(entity.force, weapon.force);
+    entity.vitesse = scale(entity.vitesse, weapon.vitesse);
+    entity.force_magique = scale(entity.force_magique, weapon.force_magique);
+    entity.weapon = Some(weapon.name.clone());
+}
+
+/// Neither an adventurer nor a creature, with the closest names of both.
+pub fn unknown_combatant(env: &Env, name: &str) -> Error {
+    let mut suggestions = env.data.bestiary.suggestions(name);
+    if let Ok(adventurers) = env.adventurers.all() {
+        suggestions.extend(names::suggestions(
+            name,
+            adventurers.iter().map(|a| a.name.as_str()),
+        ));
+    }
+    Error::UnknownCombatant {
+        name: name.to_string(),
+        suggestions,
+    }
+}
+
+fn build_entity(
+    env: &Env,
+    name: &str,
+    weapon: Option<&str>,
+    notes: &mut Vec<String>,
+) -> Result<Entity> {
+    // Adventurers take precedence over creatures of the same name.
+    if let Some(adventurer) = env.adventurers.find(name)? {
+        return armed_entity(env, &adventurer, weapon, notes);
+    }
+    let creature = env
+        .data
+        .bestiary
+        .find(name)
+        .ok_or_else(|| unknown_combatant(env, name))?;
+    let mut entity = creature_entity(creature, creature.level);
+    if let Some(weapon) = weapon {
+        apply_weapon(&mut entity, env.data.weapons.get(weapon)?);
+    }
+    debug!("{:?}", entity);
+
+    Ok(entity)
+}
+
+pub fn creature_entity(creature: &Creature, level: u8) -> Entity {
+    let stats = creature.stats(level);
+    Entity {
+        name: creature.name.clone(),
+        level,
+        force: stats.force,
+        resistance: stats.resistance,
+        vitesse: stats.vitesse,
+        resistance_magique: stats.resistance_magique,
+        force_magique: stats.force_magique,
+        equipped: false,
+        weapon: None,
+        adventurer_id: None,
+    }
+}
+
+/// Without `weapon`, an adventurer fights with the weapon they have
+/// equipped unless it is broken or no longer in the catalogue, which is
+/// noted in `notes`.
+pub fn armed_entity(
+    env: &Env,
+    adventurer: &Adventurer,
+    weapon: Option<&str>,
+    notes: &mut Vec<String>,
+) -> Result<Entity> {
+    let (weapon, equipped) = match (weapon, &adventurer.weapon) {
+        (Some(weapon), _) => (Some(env.data.weapons.get(weapon)?), false),
+        (None, Some(equipped)) if equipped.is_broken() => {
+            notes.push(format!(
+                "{} se bat à mains nues, {} est brisée.",
+                adventurer.name, equipped.name
+            ));
+            (None, false)
+        }
+        (None, Some(_)) => {
+            let weapon = adventurer.equipped_weapon(&env.data.weapons, notes);
+            (weapon, weapon.is_some())
         }
+        (None, None) => (None, false),
+    };
+    let modifiers = modifiers::of(adventurer, weapon, &env.data.effects, &env.config.duel);
+    let mut entity = adventurer_entity(env, adventurer, &modifiers)?;
+    if let Some(weapon) = weapon {
+        apply_weapon(&mut entity, weapon);
     }
-    result.entity_second = set_entity_stats(command[nb].clone());
-    println!("{:?}", result.entity_second);
-    for (i, j) in command.clone().iter().enumerate() {
-        if i > nb {
-            if get_adventurer(j.to_string()).is_some() {
-                break;
+    entity.equipped = equipped;
+    debug!("{:?}", entity);
+
+    Ok(entity)
+}
+
+/// Refuses an adventurer under an effect that forbids `restriction`.
+pub fn ensure_allowed(env: &Env, adventurer: &Adventurer, restriction: Restriction) -> Result<()> {
+    match adventurer.restricted(&env.data.effects, restriction) {
+        Some(effect) => Err(Error::Validation(format!(
+            "{} ne peut pas {} sous l'effet de {}",
+            adventurer.name, restriction, effect.name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Takes `usure` off the adventurer's equipped weapon, with a note when it
+/// crosses a warning threshold or breaks.
+pub fn wear_weapon(adventurer: &mut Adventurer, usure: f32) -> Option<String> {
+    let weapon = adventurer.weapon.as_mut()?;
+    match weapon.wear(usure) {
+        Wear::Intact => None,
+        Wear::Worn(threshold) => Some(format!(
+            "⚠️ {} de {} est sous {}% de sa durabilité: {}",
+            weapon.name, adventurer.name, threshold, weapon
+        )),
+        Wear::Broken => Some(format!(
+            "💥 {} de {} est brisée et doit être réparée par un forgeron.",
+            weapon.name, adventurer.name
+        )),
+    }
+}
+
+/// One side of a `?roll`.
+pub struct Fighter<'a> {
+    pub name: &'a str,
+    pub weapon: Option<&'a str>,
+    /// Stats given on the command line, for a name that is neither an
+    /// adventurer nor a creature of the bestiary.
+    pub improvised: Option<Entity>,
+}
+
+/// Inline stats must not stand in for a sheet, nor be recorded under its
+/// name in the history.
+pub fn ensure_unclaimed(env: &Env, name: &str) -> Result<()> {
+    let taken = match env.adventurers.find(name)? {
+        Some(_) => Some("un aventurier"),
+        None => env
+            .data
+            .bestiary
+            .find(name)
+            .map(|_| "une créature du bestiaire"),
+    };
+    match taken {
+        Some(kind) => Err(Error::Validation(format!(
+            "{} est déjà {}: retire les stats ou choisis un autre nom pour un combattant improvisé",
+            name, kind
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn fighter_entity(env: &Env, fighter: Fighter, notes: &mut Vec<String>) -> Result<Entity> {
+    let mut entity = match fighter.improvised {
+        Some(entity) => entity,
+        None => return build_entity(env, fighter.name, fighter.weapon, notes),
+    };
+    ensure_unclaimed(env, fighter.name)?;
+    if let Some(weapon) = fighter.weapon {
+        apply_weapon(&mut entity, env.data.weapons.get(weapon)?);
+    }
+    notes.push(format!("Improvisé: {}", entity));
+
+    Ok(entity)
+}
+
+/// What a recorded roll was computed from, besides its seed.
+#[derive(Serialize, Deserialize)]
+pub struct RollInputs {
+    pub entities: Entities,
+    pub diff_stats: DiffStats,
+}
+
+impl RollInputs {
+    pub fn of(record: &RollRecord) -> Result<RollInputs> {
+        serde_json::from_str(&record.inputs)
+            .map_err(|why| StorageError::Corrupted(format!("roll #{}: {}", record.id, why)).into())
+    }
+
+    /// The result of `record` as stored, or recomputed from its seed for
+    /// the rolls recorded before outcomes were kept.
+    pub fn result(&self, record: &RollRecord) -> Result<AttackResult> {
+        let outcome = match &record.outcome {
+            Some(outcome) => outcome,
+            None => {
+                return Ok(result_roll_attack(
+                    &self.diff_stats,
+                    self.entities.clone(),
+                    &mut seeded(record.seed),
+                ))
             }
-            match j.as_str() {
-                "-weapon" => match command[i + 1].as_str() {
-                    "Leger" => {
-                        result.entity_second.force *= 0.9;
-                        result.entity_second.vitesse *= 1.05;
-                    }
-                    "Moyen" => {}
-                    "Lourd" => {
-                        result.entity_second.force *= 1.1;
-                        result.entity_second.vitesse *= 0.9;
-                    }
-                    _ => {
-                        result.entity_second.force *= 0.85;
-                        result.entity_second.vitesse *= 1.075;
-                    }
-                },
-                _ => {}
+        };
+        let tier = |key: &str| {
+            key.parse::<DiffStatsState>()
+                .map_err(|why| StorageError::Corrupted(format!("roll #{}: {}", record.id, why)))
+        };
+
+        Ok(AttackResult {
+            gagnant: record.winner.clone(),
+            perdant: record.loser.clone(),
+            first_won: record.first_won,
+            diff_vitesse: tier(&outcome.speed_tier)?,
+            diff_force: tier(&outcome.attack_tier)?,
+            usure: outcome.usure,
+            die: None,
+        })
+    }
+}
+
+/// Resolves one exchange between `entities` from a fresh seed and records
+/// it with everything it was computed from, so that `?rejouer` gives the
+/// very same outcome later on.
+pub fn record_roll(env: &Env, entities: &Entities) -> Result<(RollRecord, AttackResult)> {
+    let seed = thread_rng().gen();
+    let inputs = RollInputs {
+        entities: entities.clone(),
+        diff_stats: env.data.diff_stats.clone(),
+    };
+    let result = result_roll_attack(&inputs.diff_stats, entities.clone(), &mut seeded(seed));
+    let mut record = RollRecord {
+        id: 0,
+        seed,
+        channel_id: env.channel_id.0,
+        created_at: now(),
+        first: entities.entity_one.name.clone(),
+        second: entities.entity_second.name.clone(),
+        first_id: entities.entity_one.adventurer_id,
+        second_id: entities.entity_second.adventurer_id,
+        winner: result.gagnant.clone(),
+        loser: result.perdant.clone(),
+        first_won: result.first_won,
+        outcome: Some(RollOutcome {
+            speed_tier: result.diff_vitesse.key().to_string(),
+            attack_tier: result.diff_force.key().to_string(),
+            usure: result.usure,
+        }),
+        inputs: serde_json::to_string(&inputs)
+            .map_err(|why| StorageError::Corrupted(why.to_string()))?,
+    };
+    record.id = env.rolls.record(&record)?;
+
+    Ok((record, result))
+}
+
+pub fn roll(env: &Env, first: Fighter, second: Fighter) -> Result<Reply> {
+    // Both sides fight in a roll, as in a duel.
+    for fighter in [&first, &second] {
+        if fighter.improvised.is_none() {
+            if let Some(adventurer) = env.adventurers.find(fighter.name)? {
+                ensure_allowed(env, &adventurer, Restriction::Combat)?;
             }
         }
     }
-    result.entity_one.force += 0.25 - result.entity_one.force % 0.25;
-    result.entity_one.vitesse += 0.25 - result.entity_one.vitesse % 0.25;
-    result.entity_second.force += 0.25 - result.entity_second.force % 0.25;
-    result.entity_second.vitesse += 0.25 - result.entity_second.vitesse % 0.25;
-    println!("{:?}", result.entity_one);
-    println!("{:?}", result.entity_second);
+    let mut notes = Vec::new();
+    let entities = Entities {
+        entity_one: fighter_entity(env, first, &mut notes)?,
+        entity_second: fighter_entity(env, second, &mut notes)?,
+    };
+    let (record, result) = record_roll(env, &entities)?;
+    let loser = match result.first_won {
+        true => &entities.entity_second,
+        false => &entities.entity_one,
+    };
+    if loser.equipped {
+        // Worn on the sheet as stored now, not on the copy read for the
+        // stats, so that a write since then is not undone.
+        env.adventurers.modify(&result.perdant, &mut |loser| {
+            notes.extend(wear_weapon(loser, result.usure));
+            true
+        })?;
+    }
 
-    result
+    let mut res = result.to_string();
+    for note in notes {
+        res.push('\n');
+        res.push_str(&note);
+    }
+    res.push_str(&format!(
+        "\nRoll #{} (`{}rejouer {}` pour le détail)",
+        record.id, env.config.prefix, record.id
+    ));
+
+    Ok(Reply::text(res))
 }
 
-fn result_roll_vitesse(entities: Entities, mut result: AttackResult) -> AttackResult {
-    let path = env::var("DIFF_STATS").expect("Error in the env variable");
-    let file = File::open(path);
-    let reader = BufReader::new(file.unwrap());
-    let mut data: DiffStats = serde_json::from_reader(reader).unwrap();
-    let mut roll = thread_rng();
-    match entities.entity_one.vitesse {
-        x if x > entities.entity_second.vitesse => {
-            data.faveur *= entities.entity_second.level as f32;
-            data.avantage *= entities.entity_second.level as f32;
-            data.efficace *= entities.entity_second.level as f32;
-            data.surpuissance *= entities.entity_second.level as f32;
-            data.domination *= entities.entity_second.level as f32;
-            match entities.entity_one.vitesse - entities.entity_second.vitesse {
-                x if x < data.faveur => match roll.gen_range(1..=2) {
-                    2 => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::Neutre
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::Neutre
-                    }
-                },
-                x if x < data.avantage => match roll.gen_range(1..=3) {
-                    3 => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::SousFaveur
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::Faveur
-                    }
-                },
-                x if x < data.efficace => match roll.gen_range(1..=4) {
-                    4 => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::SousAvantage
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::Avantage
-                    }
-                },
-                x if x < data.surpuissance => match roll.gen_range(1..=5) {
-                    5 => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::SousEfficace
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::Efficace
-                    }
-                },
-                x if x < data.domination => match roll.gen_range(1..=6) {
-                    6 => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::Souspuissance
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::Surpuissance
-                    }
-                },
-                x if x > data.domination => {
-                    result.gagnant = entities.entity_one.name.clone();
-                    result.diff_vitesse = DiffStatsState::Domination
-                }
-                _ => result.gagnant = entities.entity_one.name.clone(),
-            }
+// What the recorded roll ended with where `result` ends otherwise. Rolls
+// recorded before their outcome was kept only have a winner to compare.
+fn differences(record: &RollRecord, result: &AttackResult) -> Vec<String> {
+    let mut res = Vec::new();
+    if result.first_won != record.first_won {
+        res.push(format!("{} gagnant", record.winner));
+    }
+    if let Some(outcome) = &record.outcome {
+        if result.diff_vitesse.key() != outcome.speed_tier {
+            res.push(format!("vitesse {}", outcome.speed_tier));
+        }
+        if result.diff_force.key() != outcome.attack_tier {
+            res.push(format!("attaque {}", outcome.attack_tier));
+        }
+        if result.usure != outcome.usure {
+            res.push(format!("usure {}", outcome.usure));
+        }
+    }
+    res
+}
+
+/// Recomputes roll `id` from its recorded seed and inputs and shows every
+/// step of it. The wear it caused is not applied again.
+pub fn replay(env: &Env, id: u32) -> Result<Reply> {
+    let record = env
+        .rolls
+        .find(id)?
+        .ok_or_else(|| Error::Validation(format!("aucun roll #{} n'a été enregistré", id)))?;
+    let inputs = RollInputs::of(&record)?;
+    let data = &inputs.diff_stats;
+    let entities = &inputs.entities;
+    let result = result_roll_attack(data, entities.clone(), &mut seeded(record.seed));
+    let contest = speed_contest(data, entities);
+
+    let mut res = format!(
+        "**Roll #{}** du <t:{}:f>, graine `{}`\n```\n{}\n{}\n```",
+        record.id, record.created_at, record.seed, entities.entity_one, entities.entity_second
+    );
+    res.push_str(&format!(
+        "Seuils par niveau: faveur {}, avantage {}, efficace {}, surpuissance {}, domination {}\n",
+        data.faveur, data.avantage, data.efficace, data.surpuissance, data.domination
+    ));
+    res.push_str(&format!(
+        "1. Vitesse: {} {:.2} contre {} {:.2}, écart {:.2}, seuils multipliés par {} (niveau de {})\n",
+        contest.faster.name,
+        contest.faster.vitesse,
+        contest.slower.name,
+        contest.slower.vitesse,
+        contest.faster.vitesse - contest.slower.vitesse,
+        contest.slower.level,
+        contest.slower.name
+    ));
+    match (contest.sides, result.die) {
+        (Some(sides), Some(face)) => res.push_str(&format!(
+            "2. Dé à {} faces: {}, {} ne l'emporte que sur un {}\n",
+            sides, face, contest.slower.name, sides
+        )),
+        _ => res.push_str(&format!(
+            "2. Aucun dé, l'écart suffit à {}\n",
+            contest.faster.name
+        )),
+    }
+    res.push_str(&format!(
+        "3. {} gagne la vitesse avec {}\n",
+        result.gagnant, result.diff_vitesse
+    ));
+
+    let (attacker, defender) = match result.first_won {
+        true => (&entities.entity_one, &entities.entity_second),
+        false => (&entities.entity_second, &entities.entity_one),
+    };
+    let gap = attacker.force - defender.resistance;
+    // Same choice of level as `attack_tier`.
+    let scale = match gap <= 0. {
+        true => attacker,
+        false => defender,
+    };
+    res.push_str(&format!(
+        "4. Attaque: force {:.2} de {} contre résistance {:.2} de {}, écart {:.2}, seuils multipliés par {} (niveau de {})\n",
+        attacker.force, attacker.name, defender.resistance, defender.name, gap, scale.level, scale.name
+    ));
+    res.push_str(&format!(
+        "5. {} cause {}, usure {}\n",
+        result.gagnant, result.diff_force, result.usure
+    ));
+    res.push_str(&result.to_string());
+    let differences = differences(&record, &result);
+    match differences.is_empty() {
+        true => res.push_str("\nMême issue que le roll d'origine, dont l'usure n'est pas appliquée une seconde fois."),
+        false => res.push_str(&format!(
+            "\n⚠️ Le roll d'origine avait donné: {}.",
+            differences.join(", ")
+        )),
+    }
+    if record.outcome.is_none() {
+        res.push_str("\nSeul le gagnant de ce roll ancien a été enregistré.");
+    }
+
+    Ok(Reply::text(res))
+}
+
+/// How the speed contest of an exchange is decided: the faster entity wins
+/// unless a die of `sides` faces shows its highest face, in which case the
+/// slower one does. Without a die the faster entity always wins.
+struct SpeedContest<'a> {
+    faster: &'a Entity,
+    slower: &'a Entity,
+    faster_is_first: bool,
+    sides: Option<u32>,
+    faster_tier: DiffStatsState,
+    slower_tier: DiffStatsState,
+}
+
+// The thresholds are scaled by the level of the slower entity. Equal speeds
+// count the second entity as the faster one.
+fn speed_contest<'a>(diff_stats: &DiffStats, entities: &'a Entities) -> SpeedContest<'a> {
+    let faster_is_first = entities.entity_one.vitesse > entities.entity_second.vitesse;
+    let (faster, slower) = match faster_is_first {
+        true => (&entities.entity_one, &entities.entity_second),
+        false => (&entities.entity_second, &entities.entity_one),
+    };
+    let level = slower.level as f32;
+    let (sides, faster_tier, slower_tier) = match faster.vitesse - slower.vitesse {
+        x if x < diff_stats.faveur * level => {
+            (Some(2), DiffStatsState::Neutre, DiffStatsState::Neutre)
+        }
+        x if x < diff_stats.avantage * level => {
+            (Some(3), DiffStatsState::Faveur, DiffStatsState::SousFaveur)
         }
-        _ => {
-            data.faveur *= entities.entity_one.level as f32;
-            data.avantage *= entities.entity_one.level as f32;
-            data.efficace *= entities.entity_one.level as f32;
-            data.surpuissance *= entities.entity_one.level as f32;
-            data.domination *= entities.entity_one.level as f32;
-            match entities.entity_second.vitesse - entities.entity_one.vitesse {
-                x if x < data.faveur => match roll.gen_range(1..=2) {
-                    2 => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::Neutre
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::Neutre
-                    }
-                },
-                x if x < data.avantage => match roll.gen_range(1..=3) {
-                    3 => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::SousFaveur
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::Faveur
-                    }
-                },
-                x if x < data.efficace => match roll.gen_range(1..=4) {
-                    4 => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::SousAvantage
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::Avantage
-                    }
-                },
-                x if x < data.surpuissance => match roll.gen_range(1..=5) {
-                    5 => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::SousEfficace
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::Efficace
-                    }
-                },
-                x if x < data.domination => match roll.gen_range(1..=6) {
-                    6 => {
-                        result.gagnant = entities.entity_one.name.clone();
-                        result.diff_vitesse = DiffStatsState::Souspuissance
-                    }
-                    _ => {
-                        result.gagnant = entities.entity_second.name.clone();
-                        result.diff_vitesse = DiffStatsState::Surpuissance
-                    }
-                },
-                x if x > data.domination => {
-                    result.gagnant = entities.entity_second.name.clone();
-                    result.diff_vitesse = DiffStatsState::Domination
-                }
-                _ => result.gagnant = entities.entity_second.name.clone(),
+        x if x < diff_stats.efficace * level => (
+            Some(4),
+            DiffStatsState::Avantage,
+            DiffStatsState::SousAvantage,
+        ),
+        x if x < diff_stats.surpuissance * level => (
+            Some(5),
+            DiffStatsState::Efficace,
+            DiffStatsState::SousEfficace,
+        ),
+        x if x < diff_stats.domination * level => (
+            Some(6),
+            DiffStatsState::Surpuissance,
+            DiffStatsState::Souspuissance,
+        ),
+        x if x > diff_stats.domination * level => {
+            (None, DiffStatsState::Domination, DiffStatsState::Domination)
+        }
+        // Exactly on the domination threshold the faster entity wins
+        // without any tier.
+        _ => (None, DiffStatsState::Neutre, DiffStatsState::Neutre),
+    };
+
+    SpeedContest {
+        faster,
+        slower,
+        faster_is_first,
+        sides,
+        faster_tier,
+        slower_tier,
+    }
+}
+
+// Pads every column of `rows` to its widest cell.
+fn table(rows: &[Vec<String>]) -> String {
+    let mut widths = Vec::new();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(max) if *max < width => *max = width,
+                Some(_) => {}
+                None => widths.push(width),
             }
         }
     }
 
-    if result.gagnant == entities.entity_one.name {
-        result.perdant = entities.entity_second.name;
-    } else {
-        result.perdant = entities.entity_one.name;
+    let mut res = String::new();
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        res.push_str(cells.join(" | ").trim_end());
+        res.push('\n');
     }
+    res
+}
+
+/// The exact odds of every outcome of a roll between two fighters, which
+/// only depend on the speed die: the attack tier follows from the winner.
+pub fn chances(env: &Env, first: Fighter, second: Fighter) -> Result<Reply> {
+    let mut notes = Vec::new();
+    let entities = Entities {
+        entity_one: fighter_entity(env, first, &mut notes)?,
+        entity_second: fighter_entity(env, second, &mut notes)?,
+    };
+    let data = &env.data.diff_stats;
+    let contest = speed_contest(data, &entities);
+    let faster_wins = (contest.faster, contest.slower, contest.faster_tier);
+    let slower_wins = (contest.slower, contest.faster, contest.slower_tier);
+    let (sides, outcomes) = match contest.sides {
+        Some(sides) => (sides, vec![(faster_wins, sides - 1), (slower_wins, 1)]),
+        None => (1, vec![(faster_wins, 1)]),
+    };
+
+    let mut rows = vec![vec![
+        "Gagnant".to_string(),
+        "Vitesse".to_string(),
+        "Attaque".to_string(),
+        "Usure".to_string(),
+        "Chance".to_string(),
+    ]];
+    for ((winner, loser, speed), count) in outcomes {
+        let (force, usure) = attack_tier(
+            data,
+            winner.force - loser.resistance,
+            winner.level,
+            loser.level,
+        );
+        rows.push(vec![
+            winner.name.clone(),
+            speed.to_string(),
+            force.to_string(),
+            usure.to_string(),
+            format!(
+                "{}/{} ({:.1}%)",
+                count,
+                sides,
+                count as f32 * 100. / sides as f32
+            ),
+        ]);
+    }
+
+    let mut res = format!(
+        "Chances de {} contre {}:\n```\n{}```",
+        entities.entity_one.name,
+        entities.entity_second.name,
+        table(&rows)
+    );
+    for note in notes {
+        res.push('\n');
+        res.push_str(&note);
+    }
+
+    Ok(Reply::text(res))
+}
+
+fn result_roll_vitesse(
+    diff_stats: &DiffStats,
+    entities: &Entities,
+    mut result: AttackResult,
+    rng: &mut impl Rng,
+) -> AttackResult {
+    let contest = speed_contest(diff_stats, entities);
+    result.die = contest.sides.map(|sides| rng.gen_range(1..=sides));
+    let upset = contest.sides.is_some() && result.die == contest.sides;
+    let (winner, loser, tier) = match upset {
+        true => (contest.slower, contest.faster, contest.slower_tier),
+        false => (contest.faster, contest.slower, contest.faster_tier),
+    };
+    result.gagnant = winner.name.clone();
+    result.perdant = loser.name.clone();
+    result.first_won = contest.faster_is_first != upset;
+    result.diff_vitesse = tier;
 
     result
 }
 
-fn result_roll_attack(entities: Entities) -> AttackResult {
+/// Resolves one exchange, drawing the speed die from `rng`.
+pub fn result_roll_attack(
+    data: &DiffStats,
+    entities: Entities,
+    rng: &mut impl Rng,
+) -> AttackResult {
     let mut result: AttackResult = AttackResult {
         gagnant: "Aucun".to_string(),
         perdant: "Aucun".to_string(),
+        first_won: false,
         diff_vitesse: DiffStatsState::Neutre,
         diff_force: DiffStatsState::Neutre,
         usure: 0.,
+        die: None,
     };
-    result = result_roll_vitesse(entities.clone(), result);
-
-    let path = env::var("DIFF_STATS").expect("Error in the env variable");
-    let file = File::open(path);
-    let reader = BufReader::new(file.unwrap());
-    let data: DiffStats = serde_json::from_reader(reader).unwrap();
-
-    if result.gagnant == entities.entity_one.name {
-        match entities.entity_one.force - entities.entity_second.resistance {
-            x if x <= 0.0 => match x * -1. {
-                x if x < data.faveur * entities.entity_one.level as f32 => {
-                    result.diff_force = DiffStatsState::Neutre;
-                    result.usure = 3.;
-                }
-                x if x < data.avantage * entities.entity_one.level as f32 => {
-                    result.diff_force = DiffStatsState::SousFaveur;
-                    result.usure = 4.;
-                }
-                x if x < data.efficace * entities.entity_one.level as f32 => {
-                    result.diff_force = DiffStatsState::SousAvantage;
-                    result.usure = 5.;
-                }
-                x if x < data.surpuissance * entities.entity_one.level as f32 => {
-                    result.diff_force = DiffStatsState::SousEfficace;
-                    result.usure = 6.;
-                }
-                x if x < data.domination * entities.entity_one.level as f32 => {
-                    result.diff_force = DiffStatsState::Souspuissance;
-                    result.usure = 7.;
-                }
-                _ => {
-                    result.diff_force = DiffStatsState::SousDomination;
-                    result.usure = 8.;
-                }
-            },
-            x if x < data.faveur * entities.entity_second.level as f32 => {
-                result.diff_force = DiffStatsState::Neutre;
-                result.usure = 3.;
-            }
-            x if x < data.avantage * entities.entity_second.level as f32 => {
-                result.diff_force = DiffStatsState::Faveur;
-                result.usure = 2.;
-            }
-            x if x < data.efficace * entities.entity_second.level as f32 => {
-                result.diff_force = DiffStatsState::Avantage;
-                result.usure = 1.5;
-            }
-            x if x < data.surpuissance * entities.entity_second.level as f32 => {
-                result.diff_force = DiffStatsState::Efficace;
-                result.usure = 1.;
-            }
-            x if x < data.domination * entities.entity_second.level as f32 => {
-                result.diff_force = DiffStatsState::Surpuissance;
-                result.usure = 0.5;
-            }
-            _ => {
-                result.diff_force = DiffStatsState::Domination;
-                result.usure = 0.;
+    result = result_roll_vitesse(data, &entities, result, rng);
+
+    let (attacker, defender) = match result.first_won {
+        true => (&entities.entity_one, &entities.entity_second),
+        false => (&entities.entity_second, &entities.entity_one),
+    };
+    (result.diff_force, result.usure) = attack_tier(
+        data,
+        attacker.force - defender.resistance,
+        attacker.level,
+        defender.level,
+    );
+
+    result
+}
+
+// Tier of an attack whose strength exceeds the defense by `gap`, with the
+// weapon wear it causes. A gap in favour of the attacker is measured
+// against the defender's level, a gap against it by the attacker's.
+fn attack_tier(
+    data: &DiffStats,
+    gap: f32,
+    attacker_level: u8,
+    defender_level: u8,
+) -> (DiffStatsState, f32) {
+    match gap {
+        x if x <= 0.0 => match -x {
+            x if x < data.faveur * attacker_level as f32 => (DiffStatsState::Neutre, 3.),
+            x if x < data.avantage * attacker_level as f32 => (DiffStatsState::SousFaveur, 4.),
+            x if x < data.efficace * attacker_level as f32 => (DiffStatsState::SousAvantage, 5.),
+            x if x < data.surpuissance * attacker_level as f32 => {
+                (DiffStatsState::SousEfficace, 6.)
             }
+            x if x < data.domination * attacker_level as f32 => (DiffStatsState::Souspuissance, 7.),
+            _ => (DiffStatsState::SousDomination, 8.),
+        },
+        x if x < data.faveur * defender_level as f32 => (DiffStatsState::Neutre, 3.),
+        x if x < data.avantage * defender_level as f32 => (DiffStatsState::Faveur, 2.),
+        x if x < data.efficace * defender_level as f32 => (DiffStatsState::Avantage, 1.5),
+        x if x < data.surpuissance * defender_level as f32 => (DiffStatsState::Efficace, 1.),
+        x if x < data.domination * defender_level as f32 => (DiffStatsState::Surpuissance, 0.5),
+        _ => (DiffStatsState::Domination, 0.),
+    }
+}
+
+struct SpellResult {
+    lanceur: String,
+    cible: String,
+    ecole: String,
+    diff_force: DiffStatsState,
+    energie: Magic,
+}
+
+impl fmt::Display for SpellResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "```{} lance un sort de {} sur {}, son sort causant {}. Energie restante:{}```",
+            self.lanceur, self.ecole, self.cible, self.diff_force, self.energie
+        )
+    }
+}
+
+/// Resolves a spell of `school` cast by `caster` on `target`: magical force
+/// against magical resistance, through the same tiers as a physical attack.
+/// The cost is taken from the caster's energy in that school, and the cast
+/// is refused when there is not enough left.
+pub fn cast(env: &Env, caster: &str, target: &str, school: &str, cost: u8) -> Result<Reply> {
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), caster)?;
+    ensure_allowed(env, &adventurer, Restriction::Magie)?;
+    let mut notes = Vec::new();
+    let modifiers = adventurer.modifiers(&env.data, &env.config.duel, &mut notes);
+    let caster = adventurer_entity(env, &adventurer, &modifiers)?;
+    let target = set_entity_stats(env, target, &mut notes)?;
+
+    let magic = adventurer
+        .energy
+        .magical
+        .iter_mut()
+        .find(|m| m.name.eq_ignore_ascii_case(school))
+        .ok_or_else(|| {
+            Error::Validation(format!(
+                "{} ne pratique pas la magie {}",
+                caster.name, school
+            ))
+        })?;
+    if magic.actual_energy < cost {
+        return Err(Error::Validation(format!(
+            "{} n'a plus assez d'énergie en {} ({}/{}, le sort en demande {})",
+            caster.name, magic.name, magic.actual_energy, magic.energy, cost
+        )));
+    }
+    magic.actual_energy -= cost;
+    let magic = magic.clone();
+
+    let (diff_force, _) = attack_tier(
+        &env.data.diff_stats,
+        caster.force_magique - target.resistance_magique,
+        caster.level,
+        target.level,
+    );
+    env.adventurers.update(&adventurer)?;
+
+    let mut res = SpellResult {
+        lanceur: caster.name,
+        cible: target.name,
+        ecole: magic.name.clone(),
+        diff_force,
+        energie: magic,
+    }
+    .to_string();
+    for note in notes {
+        res.push('\n');
+        res.push_str(&note);
+    }
+
+    Ok(Reply::text(res))
+}
+
+fn run_sort(env: &Env, args: &Args) -> Result<Reply> {
+    let cost = match args.get("cout") {
+        Some(cost) => cost.parse().ok().filter(|cost| *cost > 0).ok_or_else(|| {
+            Error::Validation(format!("le coût doit être entre 1 et 255 (reçu: {})", cost))
+        })?,
+        None => 1,
+    };
+    cast(
+        env,
+        args.required("lanceur"),
+        args.required("cible"),
+        args.required("ecole"),
+        cost,
+    )
+}
+
+// The fighter improvised from the stats written after `arg`, if any.
+fn improvised(args: &Args, arg: &str) -> Result<Option<Entity>> {
+    let given: Vec<(&str, &str)> = STAT_FLAGS
+        .iter()
+        .filter_map(|flag| args.flag(flag, arg).map(|value| (*flag, value)))
+        .collect();
+    if given.is_empty() {
+        return Ok(None);
+    }
+
+    improvised_entity(args.required(arg), given).map(Some)
+}
+
+/// A fighter named `name` with the stats given as `(flag, value)` pairs,
+/// flags being those of `STAT_FLAGS`. Stats left out keep the value of
+/// `Entity::default`.
+pub fn improvised_entity(name: &str, given: Vec<(&str, &str)>) -> Result<Entity> {
+    let mut entity = Entity {
+        name: name.to_string(),
+        ..Default::default()
+    };
+    for (flag, value) in given {
+        if flag == "niv=" {
+            entity.level = value
+                .parse()
+                .ok()
+                .filter(|level| (1..=LEVEL_MAX).contains(level))
+                .ok_or_else(|| {
+                    Error::Validation(format!(
+                        "niv doit être un nombre entre 1 et {} (reçu: {})",
+                        LEVEL_MAX, value
+                    ))
+                })?;
+            continue;
         }
-    } else {
-        match entities.entity_second.force - entities.entity_one.resistance {
-            x if x <= 0.0 => match x * -1. {
-                x if x < data.faveur * entities.entity_second.level as f32 => {
-                    result.diff_force = DiffStatsState::Neutre;
-                    result.usure = 3.;
-                }
-                x if x < data.avantage * entities.entity_second.level as f32 => {
-                    result.diff_force = DiffStatsState::SousFaveur;
-                    result.usure = 4.;
-                }
-                x if x < data.efficace * entities.entity_second.level as f32 => {
-                    result.diff_force = DiffStatsState::SousAvantage;
-                    result.usure = 5.;
-                }
-                x if x < data.surpuissance * entities.entity_second.level as f32 => {
-                    result.diff_force = DiffStatsState::SousEfficace;
-                    result.usure = 6.;
-                }
-                x if x < data.domination * entities.entity_second.level as f32 => {
-                    result.diff_force = DiffStatsState::Souspuissance;
-                    result.usure = 7.;
-                }
-                _ => {
-                    result.diff_force = DiffStatsState::SousDomination;
-                    result.usure = 8.;
-                }
-            },
-            x if x < data.faveur * entities.entity_one.level as f32 => {
-                result.diff_force = DiffStatsState::Neutre;
-                result.usure = 3.;
-            }
-            x if x < data.avantage * entities.entity_one.level as f32 => {
-                result.diff_force = DiffStatsState::Faveur;
-                result.usure = 2.;
-            }
-            x if x < data.efficace * entities.entity_one.level as f32 => {
-                result.diff_force = DiffStatsState::Avantage;
-                result.usure = 1.5;
-            }
-            x if x < data.surpuissance * entities.entity_one.level as f32 => {
-                result.diff_force = DiffStatsState::Efficace;
-                result.usure = 1.;
-            }
-            x if x < data.domination * entities.entity_one.level as f32 => {
-                result.diff_force = DiffStatsState::Surpuissance;
-                result.usure = 0.5;
-            }
-            _ => {
-                result.diff_force = DiffStatsState::Domination;
-                result.usure = 0.;
-            }
+        // Players write decimals with a comma as often as with a dot.
+        let stat = value
+            .replace(',', ".")
+            .parse::<f32>()
+            .ok()
+            .filter(|stat| stat.is_finite() && *stat > 0.)
+            .ok_or_else(|| {
+                Error::Validation(format!(
+                    "{} doit être un nombre positif (reçu: {})",
+                    flag.trim_end_matches('='),
+                    value
+                ))
+            })?;
+        match flag {
+            "for=" => entity.force = stat,
+            "res=" => entity.resistance = stat,
+            "vit=" => entity.vitesse = stat,
+            "fmag=" => entity.force_magique = stat,
+            _ => entity.resistance_magique = stat,
         }
     }
 
-    result
+    Ok(entity)
 }
 
-pub async fn attack_roll(ctx: Context, msg: Message) {
-    let command: Vec<String> = msg
-        .content
-        .clone()
-        .split_whitespace()
-        .map(String::from)
-        .collect();
-    let attack_result: AttackResult = result_roll_attack(search_entities(command));
-    msg.channel_id.say(&ctx.http, attack_result).await;
+fn fighter<'a>(args: &'a Args, arg: &str) -> Result<Fighter<'a>> {
+    Ok(Fighter {
+        name: args.required(arg),
+        weapon: args.flag("-weapon", arg),
+        improvised: improvised(args, arg)?,
+    })
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    roll(
+        env,
+        fighter(args, "attaquant")?,
+        fighter(args, "defenseur")?,
+    )
+}
+
+fn run_replay(env: &Env, args: &Args) -> Result<Reply> {
+    let id = args.required("id");
+    let id = id.trim_start_matches('#').parse().map_err(|_| {
+        Error::Validation(format!("un roll se désigne par son numéro (reçu: {})", id))
+    })?;
+    replay(env, id)
+}
+
+fn run_chances(env: &Env, args: &Args) -> Result<Reply> {
+    chances(
+        env,
+        fighter(args, "attaquant")?,
+        fighter(args, "defenseur")?,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use danjon_bot::weapons::WeaponClass;
+
+    const DIFF_STATS: DiffStats = DiffStats {
+        faveur: 1.,
+        avantage: 2.,
+        efficace: 3.,
+        surpuissance: 4.,
+        domination: 5.,
+    };
+
+    fn entities(first_speed: f32, second_speed: f32, level: u8) -> Entities {
+        let entity = |name: &str, vitesse| Entity {
+            name: name.to_string(),
+            level,
+            vitesse,
+            ..Default::default()
+        };
+        Entities {
+            entity_one: entity("Arthur", first_speed),
+            entity_second: entity("Lyra", second_speed),
+        }
+    }
+
+    #[test]
+    fn speed_contest_sides_follow_the_gap() {
+        use DiffStatsState::*;
+        let cases = [
+            (0.5, Some(2), Neutre, Neutre),
+            (1.5, Some(3), Faveur, SousFaveur),
+            (2.5, Some(4), Avantage, SousAvantage),
+            (3.5, Some(5), Efficace, SousEfficace),
+            (4.5, Some(6), Surpuissance, Souspuissance),
+            (5., None, Neutre, Neutre),
+            (7., None, Domination, Domination),
+        ];
+        for (gap, sides, faster_tier, slower_tier) in cases {
+            let entities = entities(10. + gap, 10., 1);
+            let contest = speed_contest(&DIFF_STATS, &entities);
+            assert_eq!(contest.sides, sides, "gap {}", gap);
+            assert_eq!(contest.faster_tier, faster_tier, "gap {}", gap);
+            assert_eq!(contest.slower_tier, slower_tier, "gap {}", gap);
+            assert_eq!(contest.faster.name, "Arthur");
+        }
+    }
+
+    #[test]
+    fn speed_contest_scales_by_the_slower_level() {
+        // A gap of 3 is only a faveur against a level 2 entity.
+        let entities = entities(10., 13., 2);
+        let contest = speed_contest(&DIFF_STATS, &entities);
+        assert_eq!(contest.faster.name, "Lyra");
+        assert_eq!(contest.sides, Some(3));
+    }
+
+    #[test]
+    fn equal_speeds_count_the_second_entity_as_faster() {
+        let entities = entities(10., 10., 1);
+        let contest = speed_contest(&DIFF_STATS, &entities);
+        assert_eq!(contest.faster.name, "Lyra");
+        assert_eq!(contest.sides, Some(2));
+    }
+
+    // `?chances` gives the slower entity one chance in `sides`: the roll must
+    // agree, the slower entity winning on the highest face only.
+    #[test]
+    fn the_slower_entity_wins_on_the_highest_face_only() {
+        let entities = entities(12.5, 10., 1);
+        let sides = speed_contest(&DIFF_STATS, &entities).sides.unwrap();
+        let mut faces = vec![0; sides as usize];
+        for seed in 0..200 {
+            let result = result_roll_attack(&DIFF_STATS, entities.clone(), &mut seeded(seed));
+            let die = result.die.unwrap();
+            faces[die as usize - 1] += 1;
+            let upset = result.gagnant == "Lyra";
+            assert_eq!(upset, die == sides, "seed {}", seed);
+            assert_eq!(result.first_won, !upset, "seed {}", seed);
+        }
+        assert!(faces.iter().all(|count| *count > 0));
+    }
+
+    #[test]
+    fn replays_compare_the_whole_outcome() {
+        let entities = entities(12.5, 10., 1);
+        let result = result_roll_attack(&DIFF_STATS, entities.clone(), &mut seeded(7));
+        let mut record = RollRecord {
+            id: 1,
+            seed: 7,
+            channel_id: 0,
+            created_at: 0,
+            first: "Arthur".to_string(),
+            second: "Lyra".to_string(),
+            first_id: None,
+            second_id: None,
+            winner: result.gagnant.clone(),
+            loser: result.perdant.clone(),
+            first_won: result.first_won,
+            outcome: Some(RollOutcome {
+                speed_tier: result.diff_vitesse.key().to_string(),
+                attack_tier: result.diff_force.key().to_string(),
+                usure: result.usure,
+            }),
+            inputs: String::new(),
+        };
+        assert!(differences(&record, &result).is_empty());
+
+        let outcome = record.outcome.as_mut().unwrap();
+        outcome.attack_tier = "domination".to_string();
+        outcome.usure = result.usure + 1.;
+        assert_eq!(
+            differences(&record, &result),
+            [
+                "attaque domination".to_string(),
+                format!("usure {}", result.usure + 1.)
+            ]
+        );
+
+        record.outcome = None;
+        record.winner = result.perdant.clone();
+        record.first_won = !result.first_won;
+        assert_eq!(
+            differences(&record, &result),
+            [format!("{} gagnant", result.perdant)]
+        );
+    }
+
+    #[test]
+    fn domination_leaves_nothing_to_chance() {
+        let entities = entities(20., 10., 1);
+        for seed in 0..20 {
+            let result = result_roll_attack(&DIFF_STATS, entities.clone(), &mut seeded(seed));
+            assert_eq!(result.die, None);
+            assert_eq!(result.gagnant, "Arthur");
+        }
+    }
+
+    // `?rejouer` rolls again from the inputs `record_roll` stored as JSON:
+    // with the same seed it must find the very same outcome.
+    #[test]
+    fn recorded_inputs_replay_to_the_same_outcome() {
+        for seed in 0..50 {
+            let inputs = RollInputs {
+                entities: entities(12.5, 10., 1),
+                diff_stats: DIFF_STATS,
+            };
+            let recorded = serde_json::to_string(&inputs).unwrap();
+            let result = result_roll_attack(&inputs.diff_stats, inputs.entities, &mut seeded(seed));
+            let inputs: RollInputs = serde_json::from_str(&recorded).unwrap();
+            let replayed =
+                result_roll_attack(&inputs.diff_stats, inputs.entities, &mut seeded(seed));
+            assert_eq!(replayed.gagnant, result.gagnant, "seed {}", seed);
+            assert_eq!(replayed.die, result.die, "seed {}", seed);
+            assert_eq!(replayed.diff_force, result.diff_force, "seed {}", seed);
+            assert_eq!(replayed.usure, result.usure, "seed {}", seed);
+        }
+    }
+
+    fn weapon(force: f32, vitesse: f32) -> Weapon {
+        Weapon {
+            name: "Épée".to_string(),
+            class: WeaponClass::Moyen,
+            force,
+            vitesse,
+            force_magique: 1.,
+            durabilite: 100,
+            modificateurs: Default::default(),
+            traits: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn only_the_stats_a_weapon_scales_are_rounded() {
+        let unarmed = Entity {
+            force: 12.1,
+            vitesse: 7.3,
+            force_magique: 5.05,
+            ..Default::default()
+        };
+        let mut entity = unarmed.clone();
+        apply_weapon(&mut entity, &weapon(1., 1.));
+        assert_eq!(
+            (entity.force, entity.vitesse, entity.force_magique),
+            (unarmed.force, unarmed.vitesse, unarmed.force_magique)
+        );
+
+        let mut entity = unarmed.clone();
+        apply_weapon(&mut entity, &weapon(1.1, 1.));
+        // 13.31 rounds up to the next quarter.
+        assert_eq!(entity.force, 13.5);
+        assert_eq!(entity.vitesse, unarmed.vitesse);
+        assert_eq!(entity.force_magique, unarmed.force_magique);
+        assert_eq!(entity.weapon.as_deref(), Some("Épée"));
+    }
 }
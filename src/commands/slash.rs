@@ -0,0 +1,519 @@
+use serde_json::Value;
+use serenity::{
+    builder::{CreateApplicationCommandOption, CreateApplicationCommands},
+    model::{
+        application::{
+            command::{Command, CommandOptionType},
+            interaction::{
+                application_command::{ApplicationCommandInteraction, CommandDataOption},
+                autocomplete::AutocompleteInteraction,
+            },
+        },
+        id::GuildId,
+    },
+    prelude::*,
+};
+
+use tracing::{error, info};
+
+use danjon_bot::adventurer::{get_adventurers, Jobs, Race, Rank};
+use danjon_bot::config;
+use danjon_bot::data;
+use danjon_bot::storage;
+
+use crate::commands::args::{ArgKind, ArgSpec, Args, FlagSpec, UsageError};
+use crate::commands::{find_command, game_master_only, respond, CommandSpec, Env, Reply, COMMANDS};
+
+// Discord refuses autocomplete responses with more than 25 choices.
+const MAX_CHOICES: usize = 25;
+
+// Where the value of a slash option goes in the `Args` of its handler.
+#[derive(Clone, Copy)]
+enum Source {
+    Arg(&'static ArgSpec),
+    /// A flag, scoped to the argument it names unless that is "".
+    Flag(&'static FlagSpec, &'static str),
+}
+
+struct SlashOption {
+    name: String,
+    description: String,
+    kind: CommandOptionType,
+    required: bool,
+    source: Source,
+}
+
+// Option names are plain lowercase words: `-weapon` is `weapon`, `niv=` is
+// `niv`.
+fn flag_name(flag: &FlagSpec) -> &str {
+    flag.name.trim_start_matches('-').trim_end_matches('=')
+}
+
+/// The options of `spec`: one per argument besides keywords, then one per
+/// flag, given for each argument when the flag is scoped, as in
+/// `attaquant_weapon`.
+fn slash_options(spec: &'static CommandSpec) -> Vec<SlashOption> {
+    let args = spec.args.iter().filter(|arg| arg.kind != ArgKind::Keyword);
+    let mut res: Vec<SlashOption> = args
+        .clone()
+        .map(|arg| SlashOption {
+            name: arg.name.to_string(),
+            description: arg.description.to_string(),
+            kind: match arg.kind {
+                ArgKind::Integer => CommandOptionType::Integer,
+                _ => CommandOptionType::String,
+            },
+            required: arg.required,
+            source: Source::Arg(arg),
+        })
+        .collect();
+    for flag in spec.flags {
+        let scopes: Vec<&'static str> = match flag.scoped {
+            true => args.clone().map(|arg| arg.name).collect(),
+            false => vec![""],
+        };
+        for scope in scopes {
+            let (name, description) = match scope {
+                "" => (flag_name(flag).to_string(), flag.description.to_string()),
+                arg => (
+                    format!("{}_{}", arg, flag_name(flag)),
+                    format!("{}: {}", arg, flag.description),
+                ),
+            };
+            res.push(SlashOption {
+                name,
+                description,
+                kind: CommandOptionType::String,
+                required: false,
+                source: Source::Flag(flag, scope),
+            });
+        }
+    }
+    res
+}
+
+fn create_option<'a>(
+    o: &'a mut CreateApplicationCommandOption,
+    command: &CommandSpec,
+    option: &SlashOption,
+) -> &'a mut CreateApplicationCommandOption {
+    o.name(&option.name)
+        .description(&option.description)
+        .kind(option.kind)
+        .required(option.required)
+        .set_autocomplete(completion(command, option.source).is_some())
+}
+
+// A subcommand, or the command's own handler reached as `name`.
+fn create_subcommand<'a>(
+    o: &'a mut CreateApplicationCommandOption,
+    command: &CommandSpec,
+    name: &str,
+    spec: &'static CommandSpec,
+) -> &'a mut CreateApplicationCommandOption {
+    o.name(name)
+        .description(spec.description)
+        .kind(CommandOptionType::SubCommand);
+    for option in slash_options(spec) {
+        o.create_sub_option(|o| create_option(o, command, &option));
+    }
+    o
+}
+
+// Every command of `COMMANDS`, with the same arguments and flags as the `?`
+// commands.
+fn create_commands(commands: &mut CreateApplicationCommands) -> &mut CreateApplicationCommands {
+    for spec in COMMANDS {
+        commands.create_application_command(|c| {
+            c.name(spec.name).description(spec.description);
+            if spec.subcommands.is_empty() {
+                for option in slash_options(spec) {
+                    c.create_option(|o| create_option(o, spec, &option));
+                }
+                return c;
+            }
+            if let Some(name) = spec.default_subcommand {
+                c.create_option(|o| create_subcommand(o, spec, name, spec));
+            }
+            for sub in spec.subcommands {
+                c.create_option(|o| create_subcommand(o, spec, sub.name, sub));
+            }
+            c
+        });
+    }
+    commands
+}
+
+/// Registers the slash commands on `guild_id` when given (immediate, handy
+/// while testing) or globally otherwise.
+pub async fn register(ctx: &Context, guild_id: Option<GuildId>) {
+    let result = match guild_id {
+        Some(guild_id) => guild_id
+            .set_application_commands(&ctx.http, create_commands)
+            .await
+            .map(|commands| commands.len()),
+        None => Command::set_global_application_commands(&ctx.http, create_commands)
+            .await
+            .map(|commands| commands.len()),
+    };
+    match result {
+        Ok(count) => info!("Registered {} slash commands", count),
+        Err(why) => error!("Error registering slash commands: {:?}", why),
+    }
+}
+
+// Subcommands carry their own options one level down.
+fn leaf_options(options: &[CommandDataOption]) -> (Option<&str>, &[CommandDataOption]) {
+    match options.first() {
+        Some(sub) if sub.kind == CommandOptionType::SubCommand => {
+            (Some(sub.name.as_str()), &sub.options)
+        }
+        _ => (None, options),
+    }
+}
+
+/// The command run by `/name sub`, with its parent for a subcommand.
+fn resolve(
+    name: &str,
+    sub: Option<&str>,
+) -> Option<(Option<&'static CommandSpec>, &'static CommandSpec)> {
+    let spec = find_command(name)?;
+    match sub {
+        None => Some((None, spec)),
+        Some(sub) if spec.is_default_subcommand(sub) => Some((None, spec)),
+        Some(sub) => spec
+            .subcommands
+            .iter()
+            .find(|s| s.matches(sub))
+            .map(|sub| (Some(spec), sub)),
+    }
+}
+
+// Integers come as JSON numbers, and are read as they would be typed.
+fn option_value(options: &[CommandDataOption], name: &str) -> Option<String> {
+    match options.iter().find(|o| o.name == name)?.value.as_ref()? {
+        Value::String(value) => Some(value.clone()),
+        value => Some(value.to_string()),
+    }
+}
+
+fn slash_args(
+    spec: &'static CommandSpec,
+    options: &[CommandDataOption],
+) -> Result<Args, UsageError> {
+    let mut args = Args::default();
+    for option in slash_options(spec) {
+        let value = match option_value(options, &option.name) {
+            Some(value) => value,
+            None => continue,
+        };
+        match option.source {
+            Source::Arg(arg) => args.insert(arg, value)?,
+            Source::Flag(flag, arg) => args.insert_flag(flag, arg, value),
+        }
+    }
+    // Discord enforces required options, unless the commands it knows are
+    // older than the bot.
+    args.check_required(spec.args)?;
+
+    Ok(args)
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) {
+    // Unlike a message, an interaction must be answered even when refused.
+    let config = config::config(ctx).await;
+    if !config.allows(command.guild_id, command.channel_id) {
+        let reply = Reply::error(
+            "Salon non autorisé",
+            "Les commandes du bot ne sont pas disponibles ici.",
+        );
+        respond(ctx, command, reply).await;
+        return;
+    }
+    let (sub, options) = leaf_options(&command.data.options);
+    let (parent, spec) = match resolve(&command.data.name, sub) {
+        Some(resolved) => resolved,
+        None => {
+            let reply = Reply::text(format!("Commande inconnue: /{}", command.data.name));
+            respond(ctx, command, reply).await;
+            return;
+        }
+    };
+    let roles = command
+        .member
+        .as_ref()
+        .map(|member| member.roles.as_slice())
+        .unwrap_or_default();
+    let env = Env::load(ctx, command.channel_id, command.user.id, roles).await;
+    if (spec.game_master || parent.is_some_and(|p| p.game_master)) && !env.game_master {
+        respond(ctx, command, game_master_only()).await;
+        return;
+    }
+
+    let run = spec
+        .run
+        .expect("commands without subcommands have a handler");
+    let result = match slash_args(spec, options) {
+        Ok(args) => run(&env, &args),
+        Err(why) => Ok(Reply::error("Utilisation incorrecte", why)),
+    };
+    let reply = result.unwrap_or_else(|why| {
+        error!(
+            "Error running /{} {:?} for {} in {}: {:?}",
+            command.data.name,
+            command.data.options,
+            command.user.tag(),
+            command.channel_id,
+            why
+        );
+        Reply::from(why)
+    });
+    respond(ctx, command, reply).await;
+}
+
+// What an option is completed with.
+enum Completion {
+    Adventurers,
+    /// Adventurers and the creatures of the bestiary.
+    Fighters,
+    Creatures,
+    /// The schools of the caster typed so far.
+    Schools,
+    Races,
+    Ranks,
+    Weapons,
+    Jobs,
+    Difficulties,
+    Effects,
+}
+
+fn completion(command: &CommandSpec, source: Source) -> Option<Completion> {
+    let arg = match source {
+        Source::Flag(flag, _) if flag.name == "-weapon" => return Some(Completion::Weapons),
+        Source::Flag(..) => return None,
+        Source::Arg(arg) => arg,
+    };
+    if arg.kind == ArgKind::Race {
+        return Some(Completion::Races);
+    }
+    match arg.name {
+        "nom" | "attaquant" | "defenseur" | "lanceur" | "cible" | "forgeron" | "premier"
+        | "second" => match ["roll", "chances", "historique"].contains(&command.name) {
+            true => Some(Completion::Fighters),
+            false => Some(Completion::Adventurers),
+        },
+        "creature" => Some(Completion::Creatures),
+        "ecole" => Some(Completion::Schools),
+        "rang" => Some(Completion::Ranks),
+        "arme" => Some(Completion::Weapons),
+        "metier" => Some(Completion::Jobs),
+        "difficulte" => Some(Completion::Difficulties),
+        "effet" => Some(Completion::Effects),
+        _ => None,
+    }
+}
+
+async fn adventurer_names(ctx: &Context) -> Vec<String> {
+    let repo = storage::adventurers(ctx).await;
+    match get_adventurers(repo.as_ref()) {
+        Ok(adventurers) => adventurers.into_iter().map(|a| a.name).collect(),
+        Err(why) => {
+            error!("Error listing adventurers for autocomplete: {:?}", why);
+            Vec::new()
+        }
+    }
+}
+
+pub async fn autocomplete(ctx: &Context, interaction: &AutocompleteInteraction) {
+    let (sub, options) = leaf_options(&interaction.data.options);
+    let (command, spec) = match (
+        find_command(&interaction.data.name),
+        resolve(&interaction.data.name, sub),
+    ) {
+        (Some(command), Some((_, spec))) => (command, spec),
+        _ => return,
+    };
+    let focused = match options.iter().find(|o| o.focused) {
+        Some(focused) => focused,
+        None => return,
+    };
+    let completion = slash_options(spec)
+        .into_iter()
+        .find(|option| option.name == focused.name)
+        .and_then(|option| completion(command, option.source));
+    let typed = focused
+        .value
+        .as_ref()
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let candidates: Vec<String> = match completion {
+        Some(Completion::Adventurers) => adventurer_names(ctx).await,
+        Some(Completion::Fighters) => {
+            let mut names = adventurer_names(ctx).await;
+            let game_data = data::game_data(ctx).await.get();
+            names.extend(game_data.bestiary.creatures.iter().map(|c| c.name.clone()));
+            names
+        }
+        Some(Completion::Creatures) => {
+            let game_data = data::game_data(ctx).await.get();
+            game_data
+                .bestiary
+                .creatures
+                .iter()
+                .map(|c| c.name.clone())
+                .collect()
+        }
+        Some(Completion::Schools) => {
+            let repo = storage::adventurers(ctx).await;
+            let caster = option_value(options, "lanceur").unwrap_or_default();
+            match repo.find(&caster) {
+                Ok(Some(caster)) => caster.energy.magical.into_iter().map(|m| m.name).collect(),
+                Ok(None) => Vec::new(),
+                Err(why) => {
+                    error!("Error listing magic schools for autocomplete: {:?}", why);
+                    Vec::new()
+                }
+            }
+        }
+        Some(Completion::Races) => Race::ALL.iter().map(|r| r.key().to_string()).collect(),
+        Some(Completion::Ranks) => Rank::ALL.iter().rev().map(|r| r.to_string()).collect(),
+        Some(Completion::Weapons) => {
+            let game_data = data::game_data(ctx).await.get();
+            game_data
+                .weapons
+                .weapons
+                .iter()
+                .map(|w| w.name.clone())
+                .collect()
+        }
+        Some(Completion::Jobs) => Jobs::default()
+            .levels()
+            .into_iter()
+            .map(|(job, _)| Jobs::label(job).to_string())
+            .collect(),
+        Some(Completion::Difficulties) => {
+            let config = config::config(ctx).await;
+            config
+                .jobs
+                .difficulties
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect()
+        }
+        Some(Completion::Effects) => {
+            let game_data = data::game_data(ctx).await.get();
+            game_data
+                .effects
+                .effects
+                .iter()
+                .map(|e| e.name.clone())
+                .collect()
+        }
+        None => Vec::new(),
+    };
+
+    let response = interaction
+        .create_autocomplete_response(&ctx.http, |r| {
+            for candidate in candidates
+                .iter()
+                .filter(|c| c.to_lowercase().contains(&typed))
+                .take(MAX_CHOICES)
+            {
+                r.add_string_choice(candidate, candidate);
+            }
+            r
+        })
+        .await;
+    if let Err(why) = response {
+        error!("Error sending autocomplete choices: {:?}", why);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // What Discord refuses when the commands are registered.
+    fn check_options(path: &str, spec: &'static CommandSpec) {
+        let options = slash_options(spec);
+        assert!(options.len() <= 25, "/{}", path);
+        for (i, option) in options.iter().enumerate() {
+            assert!(
+                (1..=32).contains(&option.name.chars().count())
+                    && option
+                        .name
+                        .chars()
+                        .all(|c| c.is_lowercase() || c.is_ascii_digit() || c == '_'),
+                "/{} {}",
+                path,
+                option.name
+            );
+            assert!(
+                (1..=100).contains(&option.description.chars().count()),
+                "/{} {}",
+                path,
+                option.name
+            );
+            assert!(
+                !option.required || options[..i].iter().all(|o| o.required),
+                "/{} {} follows an optional option",
+                path,
+                option.name
+            );
+        }
+    }
+
+    #[test]
+    fn slash_commands_fit_discord_limits() {
+        for spec in COMMANDS {
+            assert!(spec.description.chars().count() <= 100, "/{}", spec.name);
+            if spec.subcommands.is_empty() {
+                check_options(spec.name, spec);
+                continue;
+            }
+            assert_eq!(
+                spec.run.is_some(),
+                spec.default_subcommand.is_some(),
+                "/{}",
+                spec.name
+            );
+            if spec.run.is_some() {
+                check_options(spec.name, spec);
+            }
+            for sub in spec.subcommands {
+                assert!(sub.description.chars().count() <= 100, "/{}", sub.name);
+                check_options(&format!("{} {}", spec.name, sub.name), sub);
+            }
+        }
+    }
+
+    #[test]
+    fn scoped_flags_are_given_for_each_argument() {
+        let roll = find_command("roll").unwrap();
+        let names: Vec<String> = slash_options(roll).into_iter().map(|o| o.name).collect();
+        for name in [
+            "attaquant",
+            "defenseur",
+            "attaquant_weapon",
+            "defenseur_weapon",
+            "attaquant_niv",
+            "defenseur_rmag",
+        ] {
+            assert!(names.iter().any(|n| n == name), "{}", name);
+        }
+        assert!(!names.iter().any(|n| n == "vs"));
+    }
+
+    #[test]
+    fn default_subcommands_run_the_command_itself() {
+        let (parent, spec) = resolve("xp", Some("voir")).unwrap();
+        assert!(parent.is_none());
+        assert_eq!(spec.name, "xp");
+        let (parent, spec) = resolve("xp", Some("donner")).unwrap();
+        assert_eq!(parent.map(|p| p.name), Some("xp"));
+        assert_eq!(spec.name, "donner");
+        assert!(resolve("xp", Some("inconnu")).is_none());
+    }
+}
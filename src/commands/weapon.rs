@@ -0,0 +1,149 @@
+use danjon_bot::adventurer::get_adventurer;
+use danjon_bot::error::{Error, Result};
+use danjon_bot::weapons::EquippedWeapon;
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{CommandSpec, Env, Reply};
+
+const NOM: ArgSpec = ArgSpec {
+    name: "nom",
+    kind: ArgKind::Text,
+    required: true,
+    description: "Nom de l'aventurier",
+};
+
+pub const ARME: CommandSpec = CommandSpec {
+    name: "arme",
+    aliases: &[],
+    description: "Gère l'arme équipée des aventuriers",
+    args: &[],
+    flags: &[],
+    subcommands: &[
+        CommandSpec {
+            name: "equiper",
+            aliases: &["équiper"],
+            description: "Équipe un aventurier d'une arme neuve du catalogue",
+            args: &[
+                NOM,
+                // Rest so that names such as "Épée longue" need no quotes.
+                ArgSpec {
+                    name: "arme",
+                    kind: ArgKind::Rest,
+                    required: true,
+                    description: "Nom de l'arme",
+                },
+            ],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_equip),
+        },
+        CommandSpec {
+            name: "retirer",
+            aliases: &[],
+            description: "Retire l'arme d'un aventurier",
+            args: &[NOM],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: true,
+            run: Some(run_unequip),
+        },
+        CommandSpec {
+            name: "reparer",
+            aliases: &["réparer"],
+            description: "Répare l'arme d'un aventurier selon le niveau de forgeron du réparateur",
+            args: &[
+                ArgSpec {
+                    name: "forgeron",
+                    kind: ArgKind::Text,
+                    required: true,
+                    description: "Aventurier qui répare",
+                },
+                NOM,
+            ],
+            flags: &[],
+            subcommands: &[],
+            default_subcommand: None,
+            game_master: false,
+            run: Some(run_repair),
+        },
+    ],
+    default_subcommand: None,
+    game_master: false,
+    run: None,
+};
+
+pub fn equip(env: &Env, name: &str, weapon: &str) -> Result<Reply> {
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let weapon = EquippedWeapon::new(env.data.weapons.get(weapon)?);
+    let res = format!("{} est équipé de {}", adventurer.name, weapon);
+    adventurer.weapon = Some(weapon);
+    env.adventurers.update(&adventurer)?;
+
+    Ok(Reply::text(res))
+}
+
+pub fn unequip(env: &Env, name: &str) -> Result<Reply> {
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let weapon = adventurer
+        .weapon
+        .take()
+        .ok_or_else(|| Error::Validation(format!("{} n'a aucune arme équipée", name)))?;
+    env.adventurers.update(&adventurer)?;
+
+    Ok(Reply::text(format!(
+        "{} a rangé {}",
+        adventurer.name, weapon.name
+    )))
+}
+
+/// Repairs the weapon of `name` by a tenth of its durability per forgeron
+/// level of `smith`, who needs at least the level asked by the weapon's
+/// class, and more when it is broken. An adventurer may repair their own.
+pub fn repair(env: &Env, smith: &str, name: &str) -> Result<Reply> {
+    let repo = env.adventurers.as_ref();
+    let smith = get_adventurer(repo, smith)?;
+    let mut adventurer = match smith.name == name {
+        true => smith.clone(),
+        false => get_adventurer(repo, name)?,
+    };
+    let weapon = adventurer
+        .weapon
+        .as_mut()
+        .ok_or_else(|| Error::Validation(format!("{} n'a aucune arme équipée", name)))?;
+    if weapon.durability >= weapon.max_durability as f32 {
+        return Err(Error::Validation(format!(
+            "{} de {} est déjà en parfait état",
+            weapon.name, name
+        )));
+    }
+    let needed = weapon.repair_level(env.data.weapons.get(&weapon.name)?.class);
+    if smith.jobs.forgeron < needed {
+        return Err(Error::Validation(format!(
+            "réparer {} demande forgeron niveau {} ({} est niveau {})",
+            weapon, needed, smith.name, smith.jobs.forgeron
+        )));
+    }
+    let restored = weapon.repair(smith.jobs.forgeron);
+    let res = format!(
+        "{} répare {} de {}: +{} de durabilité",
+        smith.name, weapon, adventurer.name, restored
+    );
+    env.adventurers.update(&adventurer)?;
+
+    Ok(Reply::text(res))
+}
+
+fn run_equip(env: &Env, args: &Args) -> Result<Reply> {
+    equip(env, args.required("nom"), args.required("arme"))
+}
+
+fn run_unequip(env: &Env, args: &Args) -> Result<Reply> {
+    unequip(env, args.required("nom"))
+}
+
+fn run_repair(env: &Env, args: &Args) -> Result<Reply> {
+    repair(env, args.required("forgeron"), args.required("nom"))
+}
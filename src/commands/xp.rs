@@ -0,0 +1,129 @@
+use danjon_bot::adventurer::{get_adventurer, Adventurer};
+use danjon_bot::error::{Error, Result};
+use danjon_bot::modifiers;
+use danjon_bot::stats::{calc_stats, Stats};
+
+use crate::commands::args::{ArgKind, ArgSpec, Args};
+use crate::commands::{CommandSpec, Env, Reply};
+
+const NOM: ArgSpec = ArgSpec {
+    name: "nom",
+    kind: ArgKind::Text,
+    required: true,
+    description: "Nom de l'aventurier",
+};
+
+pub const XP: CommandSpec = CommandSpec {
+    name: "xp",
+    aliases: &["experience", "expérience"],
+    description: "Affiche l'expérience d'un aventurier et le prochain niveau",
+    args: &[NOM],
+    flags: &[],
+    subcommands: &[CommandSpec {
+        name: "donner",
+        aliases: &[],
+        description: "Donne de l'expérience à un aventurier, qui monte de niveau s'il en a assez",
+        args: &[
+            NOM,
+            ArgSpec {
+                name: "montant",
+                kind: ArgKind::Integer,
+                required: true,
+                description: "XP gagnée",
+            },
+        ],
+        flags: &[],
+        subcommands: &[],
+        default_subcommand: None,
+        game_master: true,
+        run: Some(run_give),
+    }],
+    default_subcommand: Some("voir"),
+    game_master: false,
+    run: Some(run),
+};
+
+fn progress(env: &Env, adventurer: &Adventurer) -> String {
+    let cap = env.config.xp.level_cap(adventurer.rank);
+    match adventurer.level < cap {
+        true => format!(
+            "{} est niveau {}: {}/{} XP vers le niveau {}",
+            adventurer.name,
+            adventurer.level,
+            adventurer.xp,
+            env.config.xp.to_next(adventurer.level),
+            adventurer.level + 1
+        ),
+        false => format!(
+            "{} est niveau {}, le maximum du rang {}: {} XP en réserve",
+            adventurer.name, adventurer.level, adventurer.rank, adventurer.xp
+        ),
+    }
+}
+
+// The stats of the combat form at both levels, modifiers included. The
+// sheet is where an unknown weapon is pointed out.
+fn stats_at(env: &Env, adventurer: &Adventurer, level: u8) -> Result<Stats> {
+    let modifiers = adventurer.modifiers(&env.data, &env.config.duel, &mut Vec::new());
+    Ok(calc_stats(
+        adventurer.iv,
+        level,
+        env.data.races.get(adventurer.race.combat_form())?,
+        modifiers::total(&modifiers),
+    ))
+}
+
+pub fn xp(env: &Env, name: &str) -> Result<Reply> {
+    let adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+
+    Ok(Reply::text(progress(env, &adventurer)))
+}
+
+pub fn give(env: &Env, name: &str, amount: u32) -> Result<Reply> {
+    if amount == 0 {
+        return Err(Error::Validation(
+            "le montant d'XP doit être positif".to_string(),
+        ));
+    }
+    let mut adventurer = get_adventurer(env.adventurers.as_ref(), name)?;
+    let before = adventurer.level;
+    let gained = adventurer.gain_xp(amount, &env.config.xp);
+    env.adventurers.update(&adventurer)?;
+
+    let mut res = format!("{} gagne {} XP.\n", adventurer.name, amount);
+    if gained > 0 {
+        let old = stats_at(env, &adventurer, before)?;
+        let new = stats_at(env, &adventurer, adventurer.level)?;
+        res.push_str(&format!(
+            "**{} passe du niveau {} au niveau {}!**\n",
+            adventurer.name, before, adventurer.level
+        ));
+        for ((name, old), (_, new)) in old.fields().into_iter().zip(new.fields()) {
+            res.push_str(&format!(
+                "{} {:.2} → {:.2} ({:+.2})\n",
+                name,
+                old,
+                new,
+                new - old
+            ));
+        }
+    }
+    res.push_str(&progress(env, &adventurer));
+
+    Ok(Reply::text(res))
+}
+
+fn run(env: &Env, args: &Args) -> Result<Reply> {
+    xp(env, args.required("nom"))
+}
+
+fn run_give(env: &Env, args: &Args) -> Result<Reply> {
+    let amount = args.required("montant");
+    let amount = amount.parse().map_err(|_| {
+        Error::Validation(format!(
+            "le montant d'XP doit être un nombre positif (reçu: {})",
+            amount
+        ))
+    })?;
+    give(env, args.required("nom"), amount)
+}
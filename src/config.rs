@@ -0,0 +1,787 @@
+use serde::Deserialize;
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use serenity::prelude::*;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::Level;
+
+use crate::adventurer::{HealthState, Rank, LEVEL_MAX};
+use crate::error::{Error, Result};
+use crate::names::fold;
+
+/// Path of the configuration file when CONFIG_FILE is not set.
+pub const DEFAULT_CONFIG_FILE: &str = "config.toml";
+
+/// Level caps of `Rank::ALL`, in the same order, when `xp.level_caps`
+/// leaves them out.
+const DEFAULT_LEVEL_CAPS: [u8; 7] = [10, 20, 30, 45, 60, 80, 100];
+
+/// `jobs.difficulties` when the file leaves them out.
+const DEFAULT_DIFFICULTIES: [(&str, u32); 4] = [
+    ("facile", 8),
+    ("moyenne", 12),
+    ("difficile", 16),
+    ("extreme", 20),
+];
+
+/// Only French texts exist for now.
+pub const LANGUAGES: [&str; 1] = ["fr"];
+
+/// Layout of the TOML file. Everything is optional here so that the
+/// environment can fill in what the file leaves out:
+///
+/// ```toml
+/// language = "fr"
+/// log_level = "info"
+///
+/// [discord]
+/// token = "..."
+/// guild_id = 123456789
+/// prefix_commands = true
+///
+/// [data]
+/// races = "data/stats_race.json"
+/// diff_stats = "data/diff_stats.json"
+/// meteo = "data/meteo.json"
+/// # The files below are optional, their catalogue is empty without them.
+/// weapons = "data/weapons.json"
+/// bestiary = "data/bestiary.json"
+/// effects = "data/effects.json"
+/// ranks = "data/ranks.json"
+/// jobs = "data/jobs.json"
+/// adventurers_db = "data/danjon.sqlite"
+///
+/// [commands]
+/// prefix = "?"
+/// allowed_guilds = []
+/// allowed_channels = []
+/// game_master_roles = [123456789]
+///
+/// [weather]
+/// intemperie_chance = 0.3
+/// week_hours = 168
+/// announcement_channel = 123456789
+///
+/// [duel]
+/// rounds = 3
+/// max_rounds = 10
+/// important = 6
+/// death_door = 12
+/// mort = 18
+/// important_malus = 10
+/// death_door_malus = 25
+///
+/// [effects]
+/// tick_minutes = 60
+/// announcement_channel = 123456789
+///
+/// [xp]
+/// base = 100
+/// growth = 1.5
+/// level_caps = { F = 10, E = 20, D = 30, C = 45, B = 60, A = 80, S = 100 }
+///
+/// [jobs]
+/// die = 20
+/// level_bonus = 2
+/// xp_per_level = 50
+/// difficulties = { facile = 8, moyenne = 12, difficile = 16, extreme = 20 }
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawConfig {
+    language: Option<String>,
+    log_level: Option<String>,
+    discord: RawDiscord,
+    data: RawData,
+    commands: RawCommands,
+    weather: RawWeather,
+    duel: RawDuel,
+    effects: RawEffects,
+    xp: RawXp,
+    jobs: RawJobs,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawDiscord {
+    token: Option<String>,
+    guild_id: Option<u64>,
+    prefix_commands: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawData {
+    races: Option<PathBuf>,
+    diff_stats: Option<PathBuf>,
+    meteo: Option<PathBuf>,
+    weapons: Option<PathBuf>,
+    bestiary: Option<PathBuf>,
+    effects: Option<PathBuf>,
+    ranks: Option<PathBuf>,
+    jobs: Option<PathBuf>,
+    adventurers_db: Option<PathBuf>,
+    adventurers_json: Option<PathBuf>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawCommands {
+    prefix: Option<String>,
+    allowed_guilds: Option<Vec<u64>>,
+    allowed_channels: Option<Vec<u64>>,
+    game_master_roles: Option<Vec<u64>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawWeather {
+    intemperie_chance: Option<f64>,
+    week_hours: Option<u64>,
+    announcement_channel: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawDuel {
+    rounds: Option<u8>,
+    max_rounds: Option<u8>,
+    important: Option<u32>,
+    death_door: Option<u32>,
+    mort: Option<u32>,
+    important_malus: Option<f32>,
+    death_door_malus: Option<f32>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawEffects {
+    tick_minutes: Option<u64>,
+    announcement_channel: Option<u64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawXp {
+    base: Option<u32>,
+    growth: Option<f64>,
+    level_caps: Option<HashMap<String, u8>>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+struct RawJobs {
+    die: Option<u32>,
+    level_bonus: Option<u32>,
+    xp_per_level: Option<u32>,
+    difficulties: Option<HashMap<String, u32>>,
+}
+
+#[derive(Clone)]
+pub struct DataPaths {
+    pub races: PathBuf,
+    pub diff_stats: PathBuf,
+    pub meteo: PathBuf,
+    /// The catalogues below are empty when their file is left out, as in
+    /// configurations older than them.
+    pub weapons: Option<PathBuf>,
+    pub bestiary: Option<PathBuf>,
+    pub effects: Option<PathBuf>,
+    pub ranks: Option<PathBuf>,
+    pub jobs: Option<PathBuf>,
+}
+
+pub enum AdventurerSource {
+    Sqlite(PathBuf),
+    /// The read-only legacy file.
+    Json(PathBuf),
+}
+
+pub struct WeatherConfig {
+    /// Odds, between 0 and 1, that `?meteo` draws an intempérie rather than
+    /// a tempérie.
+    pub intemperie_chance: f64,
+    /// Real hours an in-game week lasts, which is the unit of a climat's
+    /// `duree`.
+    pub week_hours: u64,
+    /// Where weather changes are posted, if anywhere.
+    pub announcement_channel: Option<ChannelId>,
+}
+
+/// Wound points from which a duellist's `Health.state` escalates. Every
+/// exchange lost adds points according to how hard the hit was.
+pub struct DuelConfig {
+    /// Rounds of a `?duel` that does not say how many.
+    pub rounds: u8,
+    pub max_rounds: u8,
+    pub important: u32,
+    pub death_door: u32,
+    pub mort: u32,
+    /// Points taken off the race base of every stat while the adventurer
+    /// is hurt, dead or at death's door.
+    pub important_malus: f32,
+    pub death_door_malus: f32,
+}
+
+impl DuelConfig {
+    /// The state reached with `wounds` points; it never goes down.
+    pub fn health(&self, wounds: u32) -> HealthState {
+        match wounds {
+            x if x >= self.mort => HealthState::Mort,
+            x if x >= self.death_door => HealthState::DeathDoor,
+            x if x >= self.important => HealthState::Important,
+            _ => HealthState::Aucune,
+        }
+    }
+
+    /// Points taken off every stat in `state`.
+    pub fn injury_malus(&self, state: HealthState) -> f32 {
+        match state {
+            HealthState::Aucune => 0.,
+            HealthState::Important => self.important_malus,
+            HealthState::DeathDoor | HealthState::Mort => self.death_door_malus,
+        }
+    }
+
+    /// Points a duellist starts with given how hurt they already are.
+    pub fn wounds(&self, state: HealthState) -> u32 {
+        match state {
+            HealthState::Aucune => 0,
+            HealthState::Important => self.important,
+            HealthState::DeathDoor => self.death_door,
+            HealthState::Mort => self.mort,
+        }
+    }
+}
+
+pub struct EffectConfig {
+    /// Minutes between two drains of an effect's `drain`.
+    pub tick_minutes: u64,
+    /// Where drains and expired effects are posted, if anywhere.
+    pub announcement_channel: Option<ChannelId>,
+}
+
+/// The XP curve, and how far each rank lets an adventurer level up.
+pub struct XpConfig {
+    /// XP needed to leave level 1.
+    pub base: u32,
+    /// How fast the XP needed grows with the level.
+    pub growth: f64,
+    /// The highest level of each rank.
+    pub level_caps: Vec<(Rank, u8)>,
+}
+
+impl XpConfig {
+    /// XP needed to go from `level` to the next one.
+    pub fn to_next(&self, level: u8) -> u32 {
+        let xp = self.base as f64 * (level as f64).powf(self.growth);
+        (xp.round() as u32).max(1)
+    }
+
+    pub fn level_cap(&self, rank: Rank) -> u8 {
+        self.level_caps
+            .iter()
+            .find(|(r, _)| *r == rank)
+            .map_or(LEVEL_MAX, |(_, cap)| *cap)
+    }
+}
+
+/// How `?metier` skill checks are rolled, and what they teach.
+pub struct JobConfig {
+    /// Faces of the die rolled.
+    pub die: u32,
+    /// Added to the roll for each level of the job.
+    pub level_bonus: u32,
+    /// Job XP to go from level n to n + 1 is n + 1 times this.
+    pub xp_per_level: u32,
+    /// Name and score to reach of each difficulty, from the easiest.
+    pub difficulties: Vec<(String, u32)>,
+}
+
+impl JobConfig {
+    pub fn to_next(&self, level: u8) -> u32 {
+        self.xp_per_level * (level as u32 + 1)
+    }
+
+    /// The difficulty named `name`, regardless of case and accents.
+    pub fn difficulty(&self, name: &str) -> Option<&(String, u32)> {
+        let name = fold(name);
+        self.difficulties.iter().find(|(d, _)| fold(d) == name)
+    }
+}
+
+pub struct Config {
+    pub token: String,
+    /// Slash commands are registered on this guild only when set, which
+    /// makes them available immediately while testing.
+    pub guild_id: Option<GuildId>,
+    /// The `?` commands are kept during the move to slash commands;
+    /// turning them off also drops the privileged MESSAGE_CONTENT intent.
+    pub prefix_commands: bool,
+    pub data: DataPaths,
+    pub adventurers: AdventurerSource,
+    pub prefix: String,
+    /// Empty means every guild, respectively every channel.
+    pub allowed_guilds: Vec<GuildId>,
+    pub allowed_channels: Vec<ChannelId>,
+    /// Members with one of these roles may use the game master commands.
+    /// Empty means nobody may.
+    pub game_master_roles: Vec<RoleId>,
+    pub weather: WeatherConfig,
+    pub duel: DuelConfig,
+    pub effects: EffectConfig,
+    pub xp: XpConfig,
+    pub jobs: JobConfig,
+    pub language: String,
+    pub log_level: Level,
+}
+
+impl Config {
+    /// Reads CONFIG_FILE (config.toml by default, which may be absent) then
+    /// applies the environment overrides. Every missing or invalid value is
+    /// reported at once.
+    pub fn load() -> Result<Config> {
+        let mut problems = Vec::new();
+        let mut raw = match env::var("CONFIG_FILE") {
+            Ok(path) => read_file(Path::new(&path), &mut problems),
+            Err(_) if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                read_file(Path::new(DEFAULT_CONFIG_FILE), &mut problems)
+            }
+            Err(_) => RawConfig::default(),
+        };
+        apply_env(&mut raw, &mut problems);
+
+        let config = validate(raw, &mut problems);
+        match problems.is_empty() {
+            true => Ok(config.expect("a config with no problem is complete")),
+            false => Err(Error::Config(problems)),
+        }
+    }
+
+    pub fn allows(&self, guild_id: Option<GuildId>, channel_id: ChannelId) -> bool {
+        let guild_allowed = self.allowed_guilds.is_empty()
+            || guild_id.is_some_and(|id| self.allowed_guilds.contains(&id));
+        let channel_allowed =
+            self.allowed_channels.is_empty() || self.allowed_channels.contains(&channel_id);
+
+        guild_allowed && channel_allowed
+    }
+
+    pub fn is_game_master(&self, roles: &[RoleId]) -> bool {
+        roles
+            .iter()
+            .any(|role| self.game_master_roles.contains(role))
+    }
+}
+
+fn read_file(path: &Path, problems: &mut Vec<String>) -> RawConfig {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(why) => {
+            problems.push(format!("impossible de lire {}: {}", path.display(), why));
+            return RawConfig::default();
+        }
+    };
+    toml::from_str(&content).unwrap_or_else(|why| {
+        problems.push(format!("{}: {}", path.display(), why));
+        RawConfig::default()
+    })
+}
+
+fn env_override<T: FromStr>(slot: &mut Option<T>, var: &str, problems: &mut Vec<String>) {
+    if let Ok(value) = env::var(var) {
+        match value.trim().parse() {
+            Ok(value) => *slot = Some(value),
+            Err(_) => problems.push(format!("{} a une valeur invalide: {}", var, value)),
+        }
+    }
+}
+
+// Lists are written comma-separated in the environment.
+fn env_list(slot: &mut Option<Vec<u64>>, var: &str, problems: &mut Vec<String>) {
+    if let Ok(value) = env::var(var) {
+        let ids: std::result::Result<Vec<u64>, _> = value
+            .split(',')
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(str::parse)
+            .collect();
+        match ids {
+            Ok(ids) => *slot = Some(ids),
+            Err(_) => problems.push(format!(
+                "{} doit être une liste d'identifiants: {}",
+                var, value
+            )),
+        }
+    }
+}
+
+fn apply_env(raw: &mut RawConfig, problems: &mut Vec<String>) {
+    env_override(&mut raw.language, "LANGUAGE", problems);
+    env_override(&mut raw.log_level, "LOG_LEVEL", problems);
+    env_override(&mut raw.discord.token, "DISCORD_TOKEN", problems);
+    env_override(&mut raw.discord.guild_id, "DISCORD_GUILD_ID", problems);
+    // Kept from the days of the environment-only setup, where 0 meant off.
+    if let Ok(value) = env::var("PREFIX_COMMANDS") {
+        raw.discord.prefix_commands = Some(value != "0" && value != "false");
+    }
+    env_override(&mut raw.data.races, "STATS_RACE_JSON", problems);
+    env_override(&mut raw.data.diff_stats, "DIFF_STATS", problems);
+    env_override(&mut raw.data.meteo, "METEO_JSON", problems);
+    env_override(&mut raw.data.weapons, "WEAPONS_JSON", problems);
+    env_override(&mut raw.data.bestiary, "BESTIARY_JSON", problems);
+    env_override(&mut raw.data.effects, "EFFECTS_JSON", problems);
+    env_override(&mut raw.data.ranks, "RANKS_JSON", problems);
+    env_override(&mut raw.data.jobs, "JOBS_JSON", problems);
+    env_override(&mut raw.data.adventurers_db, "ADVENTURER_DB", problems);
+    env_override(&mut raw.data.adventurers_json, "ADVENTURER_JSON", problems);
+    env_override(&mut raw.commands.prefix, "COMMAND_PREFIX", problems);
+    env_list(&mut raw.commands.allowed_guilds, "ALLOWED_GUILDS", problems);
+    env_list(
+        &mut raw.commands.allowed_channels,
+        "ALLOWED_CHANNELS",
+        problems,
+    );
+    env_list(
+        &mut raw.commands.game_master_roles,
+        "GAME_MASTER_ROLES",
+        problems,
+    );
+    env_override(
+        &mut raw.weather.intemperie_chance,
+        "INTEMPERIE_CHANCE",
+        problems,
+    );
+    env_override(&mut raw.weather.week_hours, "WEATHER_WEEK_HOURS", problems);
+    env_override(
+        &mut raw.weather.announcement_channel,
+        "WEATHER_CHANNEL",
+        problems,
+    );
+    env_override(&mut raw.duel.rounds, "DUEL_ROUNDS", problems);
+    env_override(&mut raw.duel.max_rounds, "DUEL_MAX_ROUNDS", problems);
+    env_override(&mut raw.duel.important, "DUEL_IMPORTANT", problems);
+    env_override(&mut raw.duel.death_door, "DUEL_DEATH_DOOR", problems);
+    env_override(&mut raw.duel.mort, "DUEL_MORT", problems);
+    env_override(
+        &mut raw.duel.important_malus,
+        "DUEL_IMPORTANT_MALUS",
+        problems,
+    );
+    env_override(
+        &mut raw.duel.death_door_malus,
+        "DUEL_DEATH_DOOR_MALUS",
+        problems,
+    );
+    env_override(
+        &mut raw.effects.tick_minutes,
+        "EFFECT_TICK_MINUTES",
+        problems,
+    );
+    env_override(
+        &mut raw.effects.announcement_channel,
+        "EFFECT_CHANNEL",
+        problems,
+    );
+    env_override(&mut raw.xp.base, "XP_BASE", problems);
+    env_override(&mut raw.xp.growth, "XP_GROWTH", problems);
+    env_override(&mut raw.jobs.die, "JOB_DIE", problems);
+    env_override(&mut raw.jobs.level_bonus, "JOB_LEVEL_BONUS", problems);
+    env_override(&mut raw.jobs.xp_per_level, "JOB_XP_PER_LEVEL", problems);
+}
+
+fn required<T>(value: Option<T>, key: &str, var: &str, problems: &mut Vec<String>) -> Option<T> {
+    if value.is_none() {
+        problems.push(format!("{} manquant (ou la variable {})", key, var));
+    }
+    value
+}
+
+fn validate(raw: RawConfig, problems: &mut Vec<String>) -> Option<Config> {
+    let token = required(
+        raw.discord.token,
+        "discord.token",
+        "DISCORD_TOKEN",
+        problems,
+    );
+    let races = required(raw.data.races, "data.races", "STATS_RACE_JSON", problems);
+    let diff_stats = required(
+        raw.data.diff_stats,
+        "data.diff_stats",
+        "DIFF_STATS",
+        problems,
+    );
+    let meteo = required(raw.data.meteo, "data.meteo", "METEO_JSON", problems);
+
+    let adventurers = match (raw.data.adventurers_db, raw.data.adventurers_json) {
+        (Some(db), _) => Some(AdventurerSource::Sqlite(db)),
+        (None, Some(json)) => Some(AdventurerSource::Json(json)),
+        (None, None) => {
+            problems.push(
+                "data.adventurers_db ou data.adventurers_json manquant \
+                 (ou la variable ADVENTURER_DB ou ADVENTURER_JSON)"
+                    .to_string(),
+            );
+            None
+        }
+    };
+
+    let prefix = raw.commands.prefix.unwrap_or_else(|| "?".to_string());
+    if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+        problems.push(format!(
+            "commands.prefix ne doit être ni vide ni contenir d'espace: {:?}",
+            prefix
+        ));
+    }
+
+    let intemperie_chance = raw.weather.intemperie_chance.unwrap_or(0.3);
+    if !(0. ..=1.).contains(&intemperie_chance) {
+        problems.push(format!(
+            "weather.intemperie_chance doit être entre 0 et 1: {}",
+            intemperie_chance
+        ));
+    }
+
+    let week_hours = raw.weather.week_hours.unwrap_or(168);
+    if week_hours == 0 {
+        problems.push("weather.week_hours doit valoir au moins 1".to_string());
+    }
+
+    let duel = DuelConfig {
+        rounds: raw.duel.rounds.unwrap_or(3),
+        max_rounds: raw.duel.max_rounds.unwrap_or(10),
+        important: raw.duel.important.unwrap_or(6),
+        death_door: raw.duel.death_door.unwrap_or(12),
+        mort: raw.duel.mort.unwrap_or(18),
+        important_malus: raw.duel.important_malus.unwrap_or(10.),
+        death_door_malus: raw.duel.death_door_malus.unwrap_or(25.),
+    };
+    if duel.rounds == 0 || duel.rounds > duel.max_rounds {
+        problems.push(format!(
+            "duel.rounds doit être entre 1 et duel.max_rounds ({}): {}",
+            duel.max_rounds, duel.rounds
+        ));
+    }
+    if duel.important == 0 || duel.important >= duel.death_door || duel.death_door >= duel.mort {
+        problems.push(
+            "duel.important, duel.death_door et duel.mort doivent être positifs et croissants"
+                .to_string(),
+        );
+    }
+
+    if !(0. ..=duel.death_door_malus).contains(&duel.important_malus) {
+        problems.push(format!(
+            "duel.important_malus doit être entre 0 et duel.death_door_malus ({}): {}",
+            duel.death_door_malus, duel.important_malus
+        ));
+    }
+
+    let tick_minutes = raw.effects.tick_minutes.unwrap_or(60);
+    if tick_minutes == 0 {
+        problems.push("effects.tick_minutes doit valoir au moins 1".to_string());
+    }
+
+    let xp = XpConfig {
+        base: raw.xp.base.unwrap_or(100),
+        growth: raw.xp.growth.unwrap_or(1.5),
+        level_caps: level_caps(raw.xp.level_caps.unwrap_or_default(), problems),
+    };
+    if xp.base == 0 {
+        problems.push("xp.base doit valoir au moins 1".to_string());
+    }
+    if !(0. ..=4.).contains(&xp.growth) {
+        problems.push(format!("xp.growth doit être entre 0 et 4: {}", xp.growth));
+    }
+
+    let mut difficulties: Vec<(String, u32)> = match raw.jobs.difficulties {
+        Some(difficulties) => difficulties.into_iter().collect(),
+        None => DEFAULT_DIFFICULTIES
+            .iter()
+            .map(|(name, score)| (name.to_string(), *score))
+            .collect(),
+    };
+    difficulties.sort_by_key(|(_, score)| *score);
+    let job_config = JobConfig {
+        die: raw.jobs.die.unwrap_or(20),
+        level_bonus: raw.jobs.level_bonus.unwrap_or(2),
+        xp_per_level: raw.jobs.xp_per_level.unwrap_or(50),
+        difficulties,
+    };
+    if job_config.die < 2 {
+        problems.push(format!(
+            "jobs.die doit valoir au moins 2: {}",
+            job_config.die
+        ));
+    }
+    if job_config.xp_per_level == 0 {
+        problems.push("jobs.xp_per_level doit valoir au moins 1".to_string());
+    }
+    if job_config.difficulties.is_empty() {
+        problems.push("jobs.difficulties ne doit pas être vide".to_string());
+    }
+    if job_config
+        .difficulties
+        .iter()
+        .any(|(name, _)| name.is_empty() || name.contains(char::is_whitespace))
+    {
+        problems.push("jobs.difficulties: les noms doivent être des mots simples".to_string());
+    }
+
+    let language = raw.language.unwrap_or_else(|| LANGUAGES[0].to_string());
+    if !LANGUAGES.contains(&language.as_str()) {
+        problems.push(format!(
+            "langue non prise en charge: {} (disponibles: {})",
+            language,
+            LANGUAGES.join(", ")
+        ));
+    }
+
+    let log_level = raw.log_level.unwrap_or_else(|| "info".to_string());
+    let log_level = match Level::from_str(&log_level) {
+        Ok(level) => Some(level),
+        Err(_) => {
+            problems.push(format!(
+                "log_level invalide: {} (error, warn, info, debug ou trace)",
+                log_level
+            ));
+            None
+        }
+    };
+
+    Some(Config {
+        token: token?,
+        guild_id: raw.discord.guild_id.map(GuildId),
+        prefix_commands: raw.discord.prefix_commands.unwrap_or(true),
+        data: DataPaths {
+            races: races?,
+            diff_stats: diff_stats?,
+            meteo: meteo?,
+            weapons: raw.data.weapons,
+            bestiary: raw.data.bestiary,
+            effects: raw.data.effects,
+            ranks: raw.data.ranks,
+            jobs: raw.data.jobs,
+        },
+        adventurers: adventurers?,
+        prefix,
+        allowed_guilds: ids(raw.commands.allowed_guilds, GuildId),
+        allowed_channels: ids(raw.commands.allowed_channels, ChannelId),
+        game_master_roles: ids(raw.commands.game_master_roles, RoleId),
+        weather: WeatherConfig {
+            intemperie_chance,
+            week_hours,
+            announcement_channel: raw.weather.announcement_channel.map(ChannelId),
+        },
+        duel,
+        effects: EffectConfig {
+            tick_minutes,
+            announcement_channel: raw.effects.announcement_channel.map(ChannelId),
+        },
+        xp,
+        jobs: job_config,
+        language,
+        log_level: log_level?,
+    })
+}
+
+// Ranks left out keep their default cap. Caps must not go down from a rank
+// to the one above it.
+fn level_caps(raw: HashMap<String, u8>, problems: &mut Vec<String>) -> Vec<(Rank, u8)> {
+    let mut caps: Vec<(Rank, u8)> = Rank::ALL.into_iter().zip(DEFAULT_LEVEL_CAPS).collect();
+    for (rank, cap) in raw {
+        let parsed = rank.parse::<Rank>();
+        let slot = caps.iter_mut().find(|(r, _)| Ok(*r) == parsed);
+        match slot {
+            Some((_, slot)) if (1..=LEVEL_MAX).contains(&cap) => *slot = cap,
+            Some(_) => problems.push(format!(
+                "xp.level_caps.{} doit être entre 1 et {}: {}",
+                rank, LEVEL_MAX, cap
+            )),
+            None => problems.push(format!("xp.level_caps: rang inconnu: {}", rank)),
+        }
+    }
+    if caps.windows(2).any(|pair| pair[0].1 > pair[1].1) {
+        problems.push("xp.level_caps ne doit pas baisser d'un rang au rang supérieur".to_string());
+    }
+    caps
+}
+
+fn ids<T>(ids: Option<Vec<u64>>, id: fn(u64) -> T) -> Vec<T> {
+    ids.unwrap_or_default().into_iter().map(id).collect()
+}
+
+pub struct ConfigStore;
+
+impl TypeMapKey for ConfigStore {
+    type Value = Arc<Config>;
+}
+
+pub async fn config(ctx: &Context) -> Arc<Config> {
+    let data = ctx.data.read().await;
+    data.get::<ConfigStore>()
+        .expect("ConfigStore is inserted at startup")
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn xp(base: u32, growth: f64) -> XpConfig {
+        XpConfig {
+            base,
+            growth,
+            level_caps: vec![(Rank::F, 10), (Rank::E, 20)],
+        }
+    }
+
+    #[test]
+    fn to_next_follows_the_curve() {
+        let settings = xp(100, 1.5);
+        assert_eq!(settings.to_next(1), 100);
+        assert_eq!(settings.to_next(2), 283);
+        assert_eq!(settings.to_next(4), 800);
+        assert_eq!(xp(100, 0.).to_next(50), 100);
+    }
+
+    #[test]
+    fn to_next_is_never_free() {
+        assert_eq!(xp(0, 1.5).to_next(10), 1);
+    }
+
+    #[test]
+    fn level_cap_defaults_to_the_maximum() {
+        let settings = xp(100, 1.5);
+        assert_eq!(settings.level_cap(Rank::E), 20);
+        assert_eq!(settings.level_cap(Rank::S), LEVEL_MAX);
+    }
+
+    #[test]
+    fn level_caps_keep_the_defaults_left_out() {
+        let mut problems = Vec::new();
+        let raw = HashMap::from([("e".to_string(), 25)]);
+        let caps = level_caps(raw, &mut problems);
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(caps[0], (Rank::F, DEFAULT_LEVEL_CAPS[0]));
+        assert_eq!(caps[1], (Rank::E, 25));
+    }
+
+    #[test]
+    fn level_caps_refuse_unknown_ranks_and_caps_out_of_range() {
+        let mut problems = Vec::new();
+        let raw = HashMap::from([("Z".to_string(), 10), ("F".to_string(), 0)]);
+        level_caps(raw, &mut problems);
+        problems.sort();
+        assert_eq!(
+            problems,
+            [
+                format!("xp.level_caps.F doit être entre 1 et {}: 0", LEVEL_MAX),
+                "xp.level_caps: rang inconnu: Z".to_string(),
+            ]
+        );
+    }
+}
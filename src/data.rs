@@ -0,0 +1,106 @@
+use serde::de::DeserializeOwned;
+use serenity::prelude::*;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+use crate::bestiary::Bestiary;
+use crate::config::DataPaths;
+use crate::effects::EffectCatalogue;
+use crate::error::{Error, Result};
+use crate::jobs::JobCatalogue;
+use crate::ranks::RankTable;
+use crate::stats::{DiffStats, RaceTable};
+use crate::weapons::WeaponCatalogue;
+use crate::weather::Meteos;
+
+pub fn read_json<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let file = File::open(path).map_err(|why| Error::io(path, why))?;
+    serde_json::from_reader(BufReader::new(file)).map_err(|why| Error::parse(path, why))
+}
+
+/// Loads a file that may be left out of the configuration, in which case
+/// its catalogue is empty.
+fn load_optional<T: Default>(path: &Option<PathBuf>, load: fn(&Path) -> Result<T>) -> Result<T> {
+    match path {
+        Some(path) => load(path),
+        None => Ok(T::default()),
+    }
+}
+
+/// Every game data file, parsed and validated together.
+pub struct GameData {
+    pub races: RaceTable,
+    pub diff_stats: DiffStats,
+    pub meteos: Meteos,
+    pub weapons: WeaponCatalogue,
+    pub bestiary: Bestiary,
+    pub effects: EffectCatalogue,
+    pub ranks: RankTable,
+    pub jobs: JobCatalogue,
+}
+
+impl GameData {
+    pub fn load(paths: &DataPaths) -> Result<GameData> {
+        Ok(GameData {
+            races: RaceTable::load(&paths.races)?,
+            diff_stats: DiffStats::load(&paths.diff_stats)?,
+            meteos: Meteos::load(&paths.meteo)?,
+            weapons: load_optional(&paths.weapons, WeaponCatalogue::load)?,
+            bestiary: load_optional(&paths.bestiary, Bestiary::load)?,
+            effects: load_optional(&paths.effects, EffectCatalogue::load)?,
+            ranks: load_optional(&paths.ranks, RankTable::load)?,
+            jobs: load_optional(&paths.jobs, JobCatalogue::load)?,
+        })
+    }
+}
+
+/// Holds the current `GameData`. Commands take a snapshot with `get` so a
+/// reload never changes the data under a command that is running.
+pub struct GameDataCache {
+    paths: DataPaths,
+    current: RwLock<Arc<GameData>>,
+}
+
+impl GameDataCache {
+    pub fn load(paths: DataPaths) -> Result<Self> {
+        let data = GameData::load(&paths)?;
+        Ok(GameDataCache {
+            paths,
+            current: RwLock::new(Arc::new(data)),
+        })
+    }
+
+    pub fn get(&self) -> Arc<GameData> {
+        self.current
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+
+    /// Reads every file again and swaps them in only if all of them are
+    /// valid; on error the previous data stays in use.
+    pub fn reload(&self) -> Result<()> {
+        let data = Arc::new(GameData::load(&self.paths)?);
+        *self
+            .current
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner()) = data;
+
+        Ok(())
+    }
+}
+
+pub struct GameDataStore;
+
+impl TypeMapKey for GameDataStore {
+    type Value = Arc<GameDataCache>;
+}
+
+pub async fn game_data(ctx: &Context) -> Arc<GameDataCache> {
+    let data = ctx.data.read().await;
+    data.get::<GameDataStore>()
+        .expect("GameDataStore is inserted at startup")
+        .clone()
+}
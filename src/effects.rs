@@ -0,0 +1,160 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::data::read_json;
+use crate::error::{Error, Result};
+use crate::names::{fold, suggestions};
+use crate::stats::Stats;
+
+/// Something an adventurer cannot do while under an effect.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum Restriction {
+    /// Attack in a `?roll`, a duel or an encounter.
+    Combat,
+    /// Cast a spell with `?sort`.
+    Magie,
+}
+
+impl fmt::Display for Restriction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Restriction::Combat => write!(f, "attaquer"),
+            Restriction::Magie => write!(f, "lancer de sort"),
+        }
+    }
+}
+
+/// One entry of EFFECTS_JSON: a poison, a blessing, exhaustion...
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Effect {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Added to the race base of the stats like any other modifier.
+    #[serde(default)]
+    pub modificateurs: Stats,
+    /// Physical energy lost at every tick, or regained when negative.
+    #[serde(default)]
+    pub drain: i16,
+    #[serde(default)]
+    pub restrictions: Vec<Restriction>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct EffectCatalogue {
+    pub effects: Vec<Effect>,
+}
+
+impl EffectCatalogue {
+    /// Reads EFFECTS_JSON, refusing duplicated names.
+    pub fn load(path: &Path) -> Result<EffectCatalogue> {
+        let catalogue: EffectCatalogue = read_json(path)?;
+        let mut names = HashSet::new();
+        let problems: Vec<String> = catalogue
+            .effects
+            .iter()
+            .filter(|effect| !names.insert(fold(&effect.name)))
+            .map(|effect| format!("l'effet {} apparaît plusieurs fois", effect.name))
+            .collect();
+
+        match problems.is_empty() {
+            true => Ok(catalogue),
+            false => Err(Error::invalid_data(path, problems.join("\n"))),
+        }
+    }
+
+    /// Names match regardless of case and accents, like weapons.
+    pub fn find(&self, name: &str) -> Option<&Effect> {
+        let name = fold(name);
+        self.effects.iter().find(|e| fold(&e.name) == name)
+    }
+
+    pub fn get(&self, name: &str) -> Result<&Effect> {
+        self.find(name).ok_or_else(|| Error::UnknownEffect {
+            name: name.to_string(),
+            suggestions: suggestions(name, self.effects.iter().map(|e| e.name.as_str())),
+        })
+    }
+}
+
+/// An effect an adventurer is under until `expires_at`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ActiveEffect {
+    pub name: String,
+    pub expires_at: i64,
+    /// When its drain was last applied, or when it started.
+    pub last_tick: i64,
+}
+
+fn invalid_duration(text: &str) -> String {
+    format!(
+        "durée invalide: {} (un nombre suivi de m, h ou j, par exemple 2h)",
+        text
+    )
+}
+
+/// Reads a duration such as "30m", "2h" or "3j" into seconds.
+pub fn parse_duration(text: &str) -> std::result::Result<i64, String> {
+    let invalid = || invalid_duration(text);
+    let text = text.trim();
+    let split = text.len() - text.chars().last().map_or(0, char::len_utf8);
+    let (count, unit) = text.split_at(split);
+    let unit = match unit {
+        "m" => 60,
+        "h" => 3600,
+        "j" => 86400,
+        _ => return Err(invalid()),
+    };
+    match count.parse::<i64>() {
+        Ok(count) if count > 0 => count.checked_mul(unit).ok_or_else(invalid),
+        _ => Err(invalid()),
+    }
+}
+
+/// When an effect lasting `duration` from `now` ends. A duration ending past
+/// the last representable timestamp is refused like any invalid one.
+pub fn expires_at(now: i64, duration: &str) -> std::result::Result<i64, String> {
+    now.checked_add(parse_duration(duration)?)
+        .ok_or_else(|| invalid_duration(duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_duration_reads_each_unit() {
+        assert_eq!(parse_duration("30m"), Ok(1800));
+        assert_eq!(parse_duration("2h"), Ok(7200));
+        assert_eq!(parse_duration(" 3j "), Ok(259200));
+    }
+
+    #[test]
+    fn parse_duration_refuses_bad_durations() {
+        for text in ["", "h", "2", "2s", "0h", "-1h", "1.5h", "deuxh", "2é"] {
+            assert!(parse_duration(text).is_err(), "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn parse_duration_refuses_overflows() {
+        assert!(parse_duration(&format!("{}j", i64::MAX / 1000)).is_err());
+    }
+
+    #[test]
+    fn expires_at_refuses_overflows() {
+        assert_eq!(expires_at(1000, "2h"), Ok(8200));
+        assert!(expires_at(i64::MAX - 10, "1m").is_err());
+    }
+
+    #[test]
+    fn parse_duration_quotes_the_text() {
+        assert_eq!(
+            parse_duration("2s").unwrap_err(),
+            "durée invalide: 2s (un nombre suivi de m, h ou j, par exemple 2h)"
+        );
+    }
+}
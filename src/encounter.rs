@@ -0,0 +1,152 @@
+use crate::error::{Error, Result};
+use crate::stats::Stats;
+
+/// What a participant fights as, so that each turn can rebuild their stats.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Combatant {
+    /// The adventurer sheet of the same name.
+    Adventurer,
+    /// A creature of the bestiary, fought at `level`.
+    Creature { creature: String, level: u8 },
+    /// A fighter with inline stats and no sheet.
+    Improvised { level: u8, stats: Stats },
+}
+
+/// Someone taking part in an encounter, on one of its sides.
+#[derive(Debug, Clone)]
+pub struct Participant {
+    pub name: String,
+    pub combatant: Combatant,
+    /// Free name of the side, e.g. "groupe" or "monstres".
+    pub side: String,
+    /// Who they attack this turn, once declared.
+    pub target: Option<String>,
+    /// Wound points, starting from those of their health when they joined.
+    pub wounds: u32,
+    /// Too hurt to fight on.
+    pub out: bool,
+}
+
+/// A fight between any number of participants split into sides, played one
+/// turn at a time in a channel. Attacks are declared during a turn and all
+/// resolved when it ends.
+#[derive(Debug, Clone)]
+pub struct Encounter {
+    pub channel_id: u64,
+    /// Number of the turn being declared, from 1.
+    pub turn: u32,
+    pub participants: Vec<Participant>,
+}
+
+impl Encounter {
+    pub fn new(channel_id: u64) -> Self {
+        Encounter {
+            channel_id,
+            turn: 1,
+            participants: Vec::new(),
+        }
+    }
+
+    pub fn participant(&self, name: &str) -> Option<&Participant> {
+        self.participants.iter().find(|p| p.name == name)
+    }
+
+    pub fn participant_mut(&mut self, name: &str) -> Option<&mut Participant> {
+        self.participants.iter_mut().find(|p| p.name == name)
+    }
+
+    fn fighter(&self, name: &str) -> Result<&Participant> {
+        match self.participant(name) {
+            Some(participant) if participant.out => {
+                Err(Error::Validation(format!("{} est hors de combat", name)))
+            }
+            Some(participant) => Ok(participant),
+            None => Err(Error::Validation(format!(
+                "{} ne participe pas à la rencontre",
+                name
+            ))),
+        }
+    }
+
+    /// The first of `name`, `name2`, `name3`… nobody in the encounter has
+    /// yet, for creatures joining several times.
+    pub fn free_name(&self, name: &str) -> String {
+        let mut res = name.to_string();
+        let mut n = 2;
+        while self.participant(&res).is_some() {
+            res = format!("{}{}", name, n);
+            n += 1;
+        }
+        res
+    }
+
+    pub fn add(&mut self, name: &str, combatant: Combatant, side: &str, wounds: u32) -> Result<()> {
+        if let Some(participant) = self.participant(name) {
+            return Err(Error::Validation(format!(
+                "{} participe déjà à la rencontre (camp {})",
+                name, participant.side
+            )));
+        }
+        self.participants.push(Participant {
+            name: name.to_string(),
+            combatant,
+            side: side.to_string(),
+            target: None,
+            wounds,
+            out: false,
+        });
+
+        Ok(())
+    }
+
+    /// Records that `attacker` attacks `target` this turn, replacing any
+    /// earlier declaration. Both must still fight and be on opposite sides.
+    pub fn declare(&mut self, attacker: &str, target: &str) -> Result<()> {
+        if attacker == target {
+            return Err(Error::Validation(format!(
+                "{} ne peut pas s'attaquer lui-même",
+                attacker
+            )));
+        }
+        let side = &self.fighter(attacker)?.side;
+        if self.fighter(target)?.side == *side {
+            return Err(Error::Validation(format!(
+                "{} et {} sont dans le même camp",
+                attacker, target
+            )));
+        }
+        let attacker = self.participant_mut(attacker).expect("checked above");
+        attacker.target = Some(target.to_string());
+
+        Ok(())
+    }
+
+    /// The sides in the order they joined.
+    pub fn sides(&self) -> Vec<&str> {
+        let mut sides: Vec<&str> = Vec::new();
+        for participant in &self.participants {
+            if !sides.contains(&participant.side.as_str()) {
+                sides.push(&participant.side);
+            }
+        }
+        sides
+    }
+
+    /// The only side with someone still fighting, once there is one left.
+    pub fn winner(&self) -> Option<&str> {
+        let mut standing = self.sides();
+        standing.retain(|side| self.participants.iter().any(|p| p.side == *side && !p.out));
+        match standing.as_slice() {
+            [side] if self.sides().len() > 1 => Some(side),
+            _ => None,
+        }
+    }
+
+    /// Clears the declarations and moves on to the next turn.
+    pub fn next_turn(&mut self) {
+        for participant in self.participants.iter_mut() {
+            participant.target = None;
+        }
+        self.turn += 1;
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::storage::StorageError;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Everything missing or invalid in the configuration.
+    Config(Vec<String>),
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The file parsed but its content cannot be used, e.g. an empty list.
+    InvalidData {
+        path: PathBuf,
+        message: String,
+    },
+    Storage(StorageError),
+    UnknownAdventurer(String),
+    UnknownRace(String),
+    UnknownWeapon {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    UnknownCreature {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    UnknownEffect {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    UnknownJob {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    /// Neither an adventurer nor a creature of the bestiary.
+    UnknownCombatant {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    /// A value given by a player or game master was refused.
+    Validation(String),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+impl Error {
+    pub fn io(path: &Path, source: io::Error) -> Self {
+        Error::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    pub fn parse(path: &Path, why: serde_json::Error) -> Self {
+        Error::Parse {
+            path: path.to_path_buf(),
+            line: why.line(),
+            column: why.column(),
+            message: why.to_string(),
+        }
+    }
+
+    pub fn invalid_data(path: &Path, message: impl ToString) -> Self {
+        Error::InvalidData {
+            path: path.to_path_buf(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Short French title used for the error embed shown to players.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Error::Config(_) => "Configuration invalide",
+            Error::Io { .. } => "Fichier illisible",
+            Error::Parse { .. } => "Fichier invalide",
+            Error::InvalidData { .. } => "Données invalides",
+            Error::Storage(_) => "Erreur de stockage",
+            Error::UnknownAdventurer(_) => "Aventurier inconnu",
+            Error::UnknownRace(_) => "Race inconnue",
+            Error::UnknownWeapon { .. } => "Arme inconnue",
+            Error::UnknownCreature { .. } => "Créature inconnue",
+            Error::UnknownEffect { .. } => "Effet inconnu",
+            Error::UnknownJob { .. } => "Métier inconnu",
+            Error::UnknownCombatant { .. } => "Combattant inconnu",
+            Error::Validation(_) => "Valeur refusée",
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config(problems) => {
+                write!(f, "configuration invalide:")?;
+                for problem in problems {
+                    write!(f, "\n- {}", problem)?;
+                }
+                Ok(())
+            }
+            Error::Io { path, source } => {
+                write!(f, "impossible de lire {}: {}", path.display(), source)
+            }
+            Error::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "{} ligne {}, colonne {}: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+            Error::InvalidData { path, message } => write!(f, "{}: {}", path.display(), message),
+            Error::Storage(why) => write!(f, "{}", why),
+            Error::UnknownAdventurer(name) => write!(f, "aucun aventurier ne s'appelle {}", name),
+            Error::UnknownRace(race) => write!(f, "la race {} n'existe pas", race),
+            Error::UnknownWeapon { name, suggestions } => {
+                write!(f, "l'arme {} n'existe pas", name)?;
+                write_suggestions(f, suggestions)
+            }
+            Error::UnknownCreature { name, suggestions } => {
+                write!(f, "la créature {} n'est pas dans le bestiaire", name)?;
+                write_suggestions(f, suggestions)
+            }
+            Error::UnknownEffect { name, suggestions } => {
+                write!(f, "l'effet {} n'est pas dans le catalogue", name)?;
+                write_suggestions(f, suggestions)
+            }
+            Error::UnknownJob { name, suggestions } => {
+                write!(f, "le métier {} n'existe pas", name)?;
+                write_suggestions(f, suggestions)
+            }
+            Error::UnknownCombatant { name, suggestions } => {
+                write!(
+                    f,
+                    "{} n'est ni un aventurier ni une créature du bestiaire",
+                    name
+                )?;
+                write_suggestions(f, suggestions)
+            }
+            Error::Validation(why) => write!(f, "{}", why),
+        }
+    }
+}
+
+fn write_suggestions(f: &mut fmt::Formatter<'_>, suggestions: &[String]) -> fmt::Result {
+    match suggestions.is_empty() {
+        true => Ok(()),
+        false => write!(f, " (peut-être: {})", suggestions.join(", ")),
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Storage(why) => Some(why),
+            _ => None,
+        }
+    }
+}
+
+impl From<StorageError> for Error {
+    fn from(why: StorageError) -> Self {
+        Error::Storage(why)
+    }
+}
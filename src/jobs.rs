@@ -0,0 +1,157 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::adventurer::{Jobs, JOB_LEVEL_MAX};
+use crate::data::read_json;
+use crate::error::{Error, Result};
+
+/// Something a job level lets an adventurer do.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobUnlock {
+    pub niveau: u8,
+    pub debloque: String,
+}
+
+/// One entry of JOBS_JSON. `metier` is a job key such as "forgeron".
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct JobEntry {
+    pub metier: String,
+    pub paliers: Vec<JobUnlock>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct JobCatalogue {
+    pub metiers: Vec<JobEntry>,
+}
+
+impl JobCatalogue {
+    /// Reads JOBS_JSON. Jobs left out unlock nothing in particular.
+    pub fn load(path: &Path) -> Result<JobCatalogue> {
+        let catalogue: JobCatalogue = read_json(path)?;
+        let mut seen = HashSet::new();
+        let mut problems = Vec::new();
+        for entry in &catalogue.metiers {
+            if Jobs::default().level(&entry.metier).is_none() {
+                problems.push(format!("métier inconnu: {}", entry.metier));
+            }
+            if !seen.insert(entry.metier.as_str()) {
+                problems.push(format!(
+                    "le métier {} apparaît plusieurs fois",
+                    entry.metier
+                ));
+            }
+            for unlock in &entry.paliers {
+                if !(1..=JOB_LEVEL_MAX).contains(&unlock.niveau) {
+                    problems.push(format!(
+                        "{}: le niveau {} n'est pas entre 1 et {}",
+                        entry.metier, unlock.niveau, JOB_LEVEL_MAX
+                    ));
+                }
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(catalogue),
+            false => Err(Error::invalid_data(path, problems.join("\n"))),
+        }
+    }
+
+    /// What `job` unlocks, lowest levels first.
+    pub fn unlocks(&self, job: &str) -> Vec<&JobUnlock> {
+        let mut res: Vec<&JobUnlock> = self
+            .metiers
+            .iter()
+            .filter(|entry| entry.metier == job)
+            .flat_map(|entry| &entry.paliers)
+            .collect();
+        res.sort_by_key(|unlock| unlock.niveau);
+        res
+    }
+
+    /// What reaching the levels after `from` up to `to` unlocks.
+    pub fn unlocked_between(&self, job: &str, from: u8, to: u8) -> Vec<&JobUnlock> {
+        self.unlocks(job)
+            .into_iter()
+            .filter(|unlock| unlock.niveau > from && unlock.niveau <= to)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    // Writes `content` to a file of its own for `JobCatalogue::load`.
+    fn load(test: &str, content: &str) -> (PathBuf, Result<JobCatalogue>) {
+        let path =
+            std::env::temp_dir().join(format!("danjon_jobs_{}_{}.json", std::process::id(), test));
+        fs::write(&path, content).unwrap();
+        let loaded = JobCatalogue::load(&path);
+        fs::remove_file(&path).unwrap();
+        (path, loaded)
+    }
+
+    #[test]
+    fn load_sorts_unlocks_by_level() {
+        let (_, loaded) = load(
+            "valid",
+            r#"{"metiers": [{"metier": "forgeron", "paliers": [
+                {"niveau": 5, "debloque": "Armes lourdes"},
+                {"niveau": 2, "debloque": "Réparations"}
+            ]}]}"#,
+        );
+        let catalogue = loaded.unwrap();
+        let levels: Vec<u8> = catalogue
+            .unlocks("forgeron")
+            .iter()
+            .map(|u| u.niveau)
+            .collect();
+        assert_eq!(levels, [2, 5]);
+        let between = catalogue.unlocked_between("forgeron", 2, 5);
+        assert_eq!(between.len(), 1);
+        assert_eq!(between[0].debloque, "Armes lourdes");
+        assert!(catalogue.unlocks("alchimiste_pharmacien").is_empty());
+    }
+
+    #[test]
+    fn load_lists_every_problem() {
+        let (path, loaded) = load(
+            "invalid",
+            r#"{"metiers": [
+                {"metier": "forgeron", "paliers": [{"niveau": 0, "debloque": "Rien"}]},
+                {"metier": "forgeron", "paliers": []},
+                {"metier": "jongleur", "paliers": [{"niveau": 11, "debloque": "Balles"}]}
+            ]}"#,
+        );
+        match loaded {
+            Err(Error::InvalidData { path: at, message }) => {
+                assert_eq!(at, path);
+                assert_eq!(
+                    message.lines().collect::<Vec<_>>(),
+                    [
+                        format!(
+                            "forgeron: le niveau 0 n'est pas entre 1 et {}",
+                            JOB_LEVEL_MAX
+                        ),
+                        "le métier forgeron apparaît plusieurs fois".to_string(),
+                        "métier inconnu: jongleur".to_string(),
+                        format!(
+                            "jongleur: le niveau 11 n'est pas entre 1 et {}",
+                            JOB_LEVEL_MAX
+                        ),
+                    ]
+                );
+            }
+            other => panic!("expected invalid data, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn load_reports_malformed_json() {
+        let (_, loaded) = load("malformed", r#"{"metiers": [{"metier": "forgeron"}]}"#);
+        assert!(loaded.is_err());
+    }
+}
@@ -1 +1,16 @@
+pub mod adventurer;
+pub mod bestiary;
+pub mod config;
+pub mod data;
+pub mod effects;
+pub mod encounter;
+pub mod error;
+pub mod jobs;
+pub mod modifiers;
+pub mod names;
+pub mod ranks;
+pub mod rolls;
 pub mod stats;
+pub mod storage;
+pub mod weapons;
+pub mod weather;
@@ -1,49 +1,164 @@
-use dotenv;
 use serenity::{
     async_trait,
-    model::{channel::Message, gateway::Ready},
+    model::{application::interaction::Interaction, channel::Message, gateway::Ready, id::GuildId},
     prelude::*,
 };
 use std::env;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tracing::{error, info, warn};
 
 mod commands;
 
-struct Handler;
+use danjon_bot::config::{AdventurerSource, Config, ConfigStore};
+use danjon_bot::data::{GameDataCache, GameDataStore};
+use danjon_bot::error::{Error, Result};
+use danjon_bot::storage::json::{read_adventurers, JsonAdventurers};
+use danjon_bot::storage::sqlite::Database;
+use danjon_bot::storage::{
+    AdventurerRepository, AdventurerStore, EncounterStore, RollStore, WeatherStore,
+};
+
+struct Handler {
+    guild_id: Option<GuildId>,
+    prefix_commands: bool,
+    // `ready` fires again after a reconnection.
+    tasks_started: AtomicBool,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message) {
-        match msg.content.clone() {
-            s if s.starts_with("?ping") => commands::ping::run(ctx, msg).await,
-            s if s.starts_with("?meteo") => commands::meteo::meteo(ctx, msg).await,
-            s if s.starts_with("?stats") => {
-                commands::adventurer::read_adventurer_stat(ctx, msg).await
+        if !self.prefix_commands || msg.author.bot {
+            return;
+        }
+        commands::dispatch(&ctx, &msg).await;
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::ApplicationCommand(command) => commands::slash::run(&ctx, &command).await,
+            Interaction::Autocomplete(autocomplete) => {
+                commands::slash::autocomplete(&ctx, &autocomplete).await
             }
-            s if s.starts_with("?roll") => commands::roll::attack_roll(ctx, msg).await,
             _ => {}
         }
     }
 
-    async fn ready(&self, _: Context, ready: Ready) {
-        println!("{} is connected", ready.user.name)
+    async fn ready(&self, ctx: Context, ready: Ready) {
+        info!("{} is connected", ready.user.name);
+        commands::slash::register(&ctx, self.guild_id).await;
+        if !self.tasks_started.swap(true, Ordering::SeqCst) {
+            tokio::spawn(commands::meteo::run_calendar(ctx.clone()));
+            tokio::spawn(commands::effect::run_effects(ctx));
+        }
     }
 }
 
+type Stores = (Arc<dyn AdventurerRepository>, Arc<Database>);
+
+// The SQLite store is preferred; with the read-only JSON file as fallback
+// the weather calendar and the encounters only live in memory.
+fn open_stores(config: &Config) -> Result<Stores> {
+    Ok(match &config.adventurers {
+        AdventurerSource::Sqlite(path) => {
+            let db = Arc::new(Database::open(path)?);
+            (db.clone(), db)
+        }
+        AdventurerSource::Json(path) => {
+            warn!("No adventurers_db configured, the weather calendar, encounters and rolls will not be kept");
+            (
+                Arc::new(JsonAdventurers::open(path)?),
+                Arc::new(Database::open_in_memory()?),
+            )
+        }
+    })
+}
+
+// `danjon_bot import <adventurers.json>` copies the legacy sheets into the
+// configured database and exits.
+fn import_adventurers(config: &Config, json: &str) -> Result<()> {
+    let db_path = match &config.adventurers {
+        AdventurerSource::Sqlite(path) => path,
+        AdventurerSource::Json(_) => {
+            return Err(Error::Config(vec![
+                "l'import demande data.adventurers_db (ou la variable ADVENTURER_DB)".to_string(),
+            ]))
+        }
+    };
+    let db = Database::open(db_path)?;
+    let adventurers = read_adventurers(Path::new(json))?;
+    let count = db.import_adventurers(&adventurers)?;
+    info!("Imported {} adventurers into {}", count, db_path.display());
+
+    Ok(())
+}
+
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
-    let token = env::var("DISCORD_TOKEN").expect("Error in the env variable");
 
-    let intents = GatewayIntents::GUILD_MESSAGES
-        | GatewayIntents::DIRECT_MESSAGES
-        | GatewayIntents::MESSAGE_CONTENT;
+    // Logging depends on the configuration, so its errors are printed.
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(why) => {
+            eprintln!("Startup error: {}", why);
+            return;
+        }
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(config.log_level)
+        .init();
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 3 && args[1] == "import" {
+        if let Err(why) = import_adventurers(&config, &args[2]) {
+            error!("Import failed, nothing was written: {}", why);
+        }
+        return;
+    }
+
+    if config.game_master_roles.is_empty() {
+        warn!("No commands.game_master_roles configured, nobody may use the game master commands");
+    }
+
+    let (adventurers, db) = match open_stores(&config) {
+        Ok(stores) => stores,
+        Err(why) => {
+            error!("Startup error: {}", why);
+            return;
+        }
+    };
+    let game_data = match GameDataCache::load(config.data.clone()) {
+        Ok(game_data) => Arc::new(game_data),
+        Err(why) => {
+            error!("Startup error: {}", why);
+            return;
+        }
+    };
+
+    let mut intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::DIRECT_MESSAGES;
+    if config.prefix_commands {
+        intents |= GatewayIntents::MESSAGE_CONTENT;
+    }
 
-    let mut client = Client::builder(&token, intents)
-        .event_handler(Handler)
+    let mut client = Client::builder(&config.token, intents)
+        .event_handler(Handler {
+            guild_id: config.guild_id,
+            prefix_commands: config.prefix_commands,
+            tasks_started: AtomicBool::new(false),
+        })
+        .type_map_insert::<ConfigStore>(config.clone())
+        .type_map_insert::<AdventurerStore>(adventurers)
+        .type_map_insert::<WeatherStore>(db.clone())
+        .type_map_insert::<EncounterStore>(db.clone())
+        .type_map_insert::<RollStore>(db)
+        .type_map_insert::<GameDataStore>(game_data)
         .await
         .expect("Err creating client");
 
     if let Err(why) = client.start().await {
-        println!("Client error: {:?}", why);
+        error!("Client error: {:?}", why);
     }
 }
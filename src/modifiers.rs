@@ -0,0 +1,228 @@
+use crate::adventurer::{Adventurer, HealthState};
+use crate::config::DuelConfig;
+use crate::effects::{Effect, EffectCatalogue};
+use crate::stats::{calc_stats, IvStats, Stats, StatsRace};
+use crate::weapons::Weapon;
+
+/// Points added to (or taken off) the race base of some stats, before the
+/// level formula of `calc_stats`.
+#[derive(Debug, Clone)]
+pub struct Modifier {
+    /// Where it comes from, as shown on the sheet.
+    pub label: String,
+    pub stats: Stats,
+}
+
+impl Modifier {
+    /// The `modificateurs` of a weapon, if it has any.
+    pub fn weapon(weapon: &Weapon) -> Option<Modifier> {
+        (weapon.modificateurs != Stats::default()).then(|| Modifier {
+            label: weapon.name.clone(),
+            stats: weapon.modificateurs.clone(),
+        })
+    }
+
+    /// The `modificateurs` of an effect, if it has any.
+    pub fn effect(effect: &Effect) -> Option<Modifier> {
+        (effect.modificateurs != Stats::default()).then(|| Modifier {
+            label: effect.name.clone(),
+            stats: effect.modificateurs.clone(),
+        })
+    }
+
+    /// The malus of being hurt, the same on every stat.
+    pub fn injury(state: HealthState, settings: &DuelConfig) -> Option<Modifier> {
+        let malus = settings.injury_malus(state);
+        (malus != 0.).then(|| Modifier {
+            label: format!("blessure ({})", state),
+            stats: Stats {
+                force: -malus,
+                resistance: -malus,
+                vitesse: -malus,
+                force_magique: -malus,
+                resistance_magique: -malus,
+            },
+        })
+    }
+}
+
+/// What weighs on the stats of `adventurer` when fighting with `weapon`.
+/// Effects missing from `effects` are left out.
+pub fn of(
+    adventurer: &Adventurer,
+    weapon: Option<&Weapon>,
+    effects: &EffectCatalogue,
+    settings: &DuelConfig,
+) -> Vec<Modifier> {
+    let effects = adventurer
+        .effects
+        .iter()
+        .filter_map(|active| effects.find(&active.name))
+        .filter_map(Modifier::effect);
+    weapon
+        .and_then(Modifier::weapon)
+        .into_iter()
+        .chain(effects)
+        .chain(Modifier::injury(adventurer.health.state, settings))
+        .collect()
+}
+
+/// The `stats_mod` of `calc_stats`, `None` without any modifier.
+pub fn total(modifiers: &[Modifier]) -> Option<Stats> {
+    modifiers
+        .iter()
+        .map(|modifier| modifier.stats.clone())
+        .reduce(|total, stats| total.zip(&stats, |a, b| a + b))
+}
+
+/// Final stats and how much each modifier changed them.
+pub struct Breakdown {
+    pub base: Stats,
+    /// Each label with its share of the final stats. The shares add up to
+    /// the final stats exactly, rounding included.
+    pub parts: Vec<(String, Stats)>,
+    pub total: Stats,
+}
+
+impl Breakdown {
+    pub fn new(iv: IvStats, level: u8, race: &StatsRace, modifiers: &[Modifier]) -> Self {
+        let base = calc_stats(iv, level, race, None);
+        let mut previous = base.clone();
+        let mut parts = Vec::new();
+        // Each share is what adding the modifier changes, given the ones
+        // before it, so that rounding is accounted for once.
+        for (i, modifier) in modifiers.iter().enumerate() {
+            let stats = calc_stats(iv, level, race, total(&modifiers[..=i]));
+            parts.push((modifier.label.clone(), stats.zip(&previous, |a, b| a - b)));
+            previous = stats;
+        }
+
+        Breakdown {
+            base,
+            parts,
+            total: previous,
+        }
+    }
+
+    /// One line per stat changed by a modifier, such as
+    /// "Force 12.25 = base 10.75 + Épée longue 1.50".
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (i, (name, total)) in self.total.fields().into_iter().enumerate() {
+            let mut line = format!(
+                "{} {:.2} = base {:.2}",
+                name,
+                total,
+                self.base.fields()[i].1
+            );
+            let mut changed = false;
+            for (label, stats) in &self.parts {
+                let share = stats.fields()[i].1;
+                if share == 0. {
+                    continue;
+                }
+                changed = true;
+                match share > 0. {
+                    true => line.push_str(&format!(" + {} {:.2}", label, share)),
+                    false => line.push_str(&format!(" - {} {:.2}", label, -share)),
+                }
+            }
+            if changed {
+                lines.push(line);
+            }
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn race() -> StatsRace {
+        StatsRace {
+            race: "Humain".to_string(),
+            force: 50,
+            resistance: 40,
+            vitesse: 40,
+            force_magique: 40,
+            resistance_magique: 40,
+        }
+    }
+
+    fn modifier(label: &str, stats: Stats) -> Modifier {
+        Modifier {
+            label: label.to_string(),
+            stats,
+        }
+    }
+
+    fn modifiers() -> Vec<Modifier> {
+        let malus = Stats {
+            force: -1.,
+            resistance: -1.,
+            vitesse: -1.,
+            force_magique: -1.,
+            resistance_magique: -1.,
+        };
+        vec![
+            modifier(
+                "Épée longue",
+                Stats {
+                    force: 3.,
+                    ..Default::default()
+                },
+            ),
+            modifier("blessure (Important)", malus),
+        ]
+    }
+
+    #[test]
+    fn total_adds_up_the_modifiers() {
+        assert_eq!(total(&[]), None);
+        let total = total(&modifiers()).unwrap();
+        assert_eq!(total.force, 2.);
+        assert_eq!(total.vitesse, -1.);
+    }
+
+    #[test]
+    fn lines_detail_each_changed_stat() {
+        // At level 73 a point of race base is worth exactly a point of stat.
+        let breakdown = Breakdown::new(IvStats::default(), 73, &race(), &modifiers());
+        let lines = breakdown.lines();
+        assert_eq!(
+            lines[..2],
+            [
+                "Force 57.25 = base 55.25 + Épée longue 3.00 - blessure (Important) 1.00",
+                "Résistance 44.25 = base 45.25 - blessure (Important) 1.00",
+            ]
+        );
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn lines_leave_out_unchanged_stats() {
+        let breakdown = Breakdown::new(IvStats::default(), 73, &race(), &modifiers()[..1]);
+        assert_eq!(
+            breakdown.lines(),
+            ["Force 58.25 = base 55.25 + Épée longue 3.00"]
+        );
+        let breakdown = Breakdown::new(IvStats::default(), 73, &race(), &[]);
+        assert!(breakdown.lines().is_empty());
+        assert_eq!(breakdown.total, breakdown.base);
+    }
+
+    #[test]
+    fn shares_add_up_to_the_total() {
+        let breakdown = Breakdown::new(IvStats::default(), 10, &race(), &modifiers());
+        let sum = breakdown
+            .parts
+            .iter()
+            .fold(breakdown.base.clone(), |sum, (_, share)| {
+                sum.zip(share, |a, b| a + b)
+            });
+        for ((_, sum), (_, total)) in sum.fields().into_iter().zip(breakdown.total.fields()) {
+            assert!((sum - total).abs() < 1e-4, "{} != {}", sum, total);
+        }
+    }
+}
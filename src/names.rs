@@ -0,0 +1,59 @@
+//! Lenient matching of names typed by players against those of data files.
+
+// How many close names an unknown name error proposes.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Lowercases and drops accents, so that "baton" matches "Bâton".
+pub fn fold(name: &str) -> String {
+    name.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'à' | 'â' | 'ä' => 'a',
+            'é' | 'è' | 'ê' | 'ë' => 'e',
+            'î' | 'ï' => 'i',
+            'ô' | 'ö' => 'o',
+            'ù' | 'û' | 'ü' => 'u',
+            'ç' => 'c',
+            c => c,
+        })
+        .collect()
+}
+
+/// The candidates closest to `name`, containing it or within a few typos.
+pub fn suggestions<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let name = fold(name);
+    let mut close: Vec<(usize, &str)> = candidates
+        .map(|candidate| {
+            let folded = fold(candidate);
+            let distance = match folded.contains(&name) {
+                true => 0,
+                false => distance(&name, &folded),
+            };
+            (distance, candidate)
+        })
+        .filter(|(distance, candidate)| *distance <= candidate.chars().count() / 2)
+        .collect();
+    close.sort();
+
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+// Levenshtein distance, counted in characters.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::adventurer::{Adventurer, Rank};
+use crate::data::read_json;
+use crate::error::{Error, Result};
+
+/// One entry of RANKS_JSON: what an adventurer needs to be promoted to
+/// `rang` from the rank just below.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RankRequirement {
+    pub rang: Rank,
+    #[serde(default)]
+    pub niveau: u8,
+    /// Completed quests, as counted on the sheet.
+    #[serde(default)]
+    pub quetes: u32,
+    /// Whether the promotion is left to the judgement of the game master
+    /// granting it, beyond the level and quests. Every promotion is granted
+    /// by a game master.
+    #[serde(default)]
+    pub approbation: bool,
+}
+
+impl RankRequirement {
+    /// The level and quests required, each with whether `adventurer` has
+    /// them. The approval is up to the game master running the promotion.
+    pub fn progress(&self, adventurer: &Adventurer) -> [(bool, String); 2] {
+        [
+            (
+                adventurer.level >= self.niveau,
+                format!("niveau {} (actuel: {})", self.niveau, adventurer.level),
+            ),
+            (
+                adventurer.quests >= self.quetes,
+                format!(
+                    "{} quêtes accomplies (actuel: {})",
+                    self.quetes, adventurer.quests
+                ),
+            ),
+        ]
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct RankTable {
+    pub rangs: Vec<RankRequirement>,
+}
+
+impl RankTable {
+    /// Reads RANKS_JSON, which must list every rank above F exactly once.
+    pub fn load(path: &Path) -> Result<RankTable> {
+        let table: RankTable = read_json(path)?;
+        let mut problems = Vec::new();
+        for rank in Rank::ALL {
+            let count = table.rangs.iter().filter(|r| r.rang == rank).count();
+            match (rank, count) {
+                (Rank::F, 0) => {}
+                (Rank::F, _) => problems.push("le rang F est celui de départ".to_string()),
+                (_, 1) => {}
+                (_, 0) => problems.push(format!("le rang {} n'a pas de conditions", rank)),
+                (_, _) => problems.push(format!("le rang {} apparaît plusieurs fois", rank)),
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(table),
+            false => Err(Error::invalid_data(path, problems.join("\n"))),
+        }
+    }
+
+    /// What a promotion from `rank` requires, none from S.
+    pub fn next(&self, rank: Rank) -> Option<&RankRequirement> {
+        let next = rank.next()?;
+        self.rangs.iter().find(|r| r.rang == next)
+    }
+}
+
+/// A rank an adventurer was promoted to, and by whom.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Promotion {
+    pub from: Rank,
+    pub to: Rank,
+    /// Discord id of the member who ran the promotion.
+    pub approved_by: u64,
+    pub promoted_at: i64,
+}
@@ -0,0 +1,80 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// The generator every recorded roll draws from. The same seed gives the
+/// same draws, which is what lets `?rejouer` replay a roll.
+pub fn seeded(seed: u64) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(seed)
+}
+
+/// How a recorded roll ended, as the bot wrote it.
+#[derive(Debug, Clone)]
+pub struct RollOutcome {
+    /// Keys of the speed and attack tiers reached by the winner.
+    pub speed_tier: String,
+    pub attack_tier: String,
+    /// Durability lost by the loser's weapon, if they fought with one.
+    pub usure: f32,
+}
+
+/// A roll kept so that its outcome can be checked afterwards.
+#[derive(Debug, Clone)]
+pub struct RollRecord {
+    /// Given by the storage when the roll is recorded, 0 before that.
+    pub id: u32,
+    pub seed: u64,
+    pub channel_id: u64,
+    pub created_at: i64,
+    pub first: String,
+    pub second: String,
+    /// Ids of the adventurer sheets on each side, none for creatures and
+    /// improvised fighters.
+    pub first_id: Option<u32>,
+    pub second_id: Option<u32>,
+    pub winner: String,
+    pub loser: String,
+    /// Which side won, which the names cannot tell when both sides share
+    /// one.
+    pub first_won: bool,
+    /// Missing from the rolls recorded before outcomes were kept, which
+    /// can still be recomputed from their seed.
+    pub outcome: Option<RollOutcome>,
+    /// Everything the outcome was computed from (entities with their stats
+    /// and weapons applied, and the DIFF_STATS thresholds of the time), as
+    /// JSON written by the bot.
+    pub inputs: String,
+}
+
+impl RollRecord {
+    /// Whether `subject` fought on the first side. When both sides match,
+    /// the subject is taken as the first.
+    pub fn is_first(&self, subject: &Subject) -> bool {
+        match subject {
+            Subject::Adventurer(id) => self.first_id == Some(*id),
+            Subject::Named(name) => self.first_id.is_none() && self.first == *name,
+        }
+    }
+
+    pub fn won(&self, subject: &Subject) -> bool {
+        self.is_first(subject) == self.first_won
+    }
+}
+
+/// Whose rolls to look up: an adventurer by the id of their sheet, so that
+/// their history survives a rename, or anyone else by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Subject {
+    Adventurer(u32),
+    Named(String),
+}
+
+/// Wins, losses and attack tiers over every roll of a subject.
+#[derive(Debug, Default)]
+pub struct RollTally {
+    pub wins: u32,
+    pub losses: u32,
+    /// Keys of the attack tiers of the hits dealt, with how many there
+    /// were. Rolls recorded before outcomes were kept are left out.
+    pub dealt: Vec<(String, u32)>,
+    pub taken: Vec<(String, u32)>,
+}
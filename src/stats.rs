@@ -1,16 +1,22 @@
 use serde::{Deserialize, Serialize};
-use std::fs::File;
-use std::io::BufReader;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
 use std::vec::Vec;
-use std::{env, fmt};
+
+use crate::adventurer::Race;
+use crate::data::read_json;
+use crate::error::{Error, Result};
 
 #[derive(Serialize, Deserialize, Debug)]
 struct StatsRaces {
     data: Vec<StatsRace>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct StatsRace {
+    /// Absent from the base stats of a creature of the bestiary.
+    #[serde(default)]
     pub race: String,
     pub force: u8,
     pub resistance: u8,
@@ -19,7 +25,39 @@ pub struct StatsRace {
     pub resistance_magique: u8,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+/// Gaps between two stats, per level of the opponent, from which a roll
+/// gets a faveur, an avantage and so on. Read from DIFF_STATS.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DiffStats {
+    pub faveur: f32,
+    pub avantage: f32,
+    pub efficace: f32,
+    pub surpuissance: f32,
+    pub domination: f32,
+}
+
+impl DiffStats {
+    pub fn load(path: &Path) -> Result<DiffStats> {
+        let data: DiffStats = read_json(path)?;
+        let steps = [
+            data.faveur,
+            data.avantage,
+            data.efficace,
+            data.surpuissance,
+            data.domination,
+        ];
+        if steps[0] <= 0. || steps.windows(2).any(|w| w[0] >= w[1]) {
+            return Err(Error::invalid_data(
+                path,
+                "les seuils doivent être positifs et croissants de faveur à domination",
+            ));
+        }
+
+        Ok(data)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default)]
 pub struct IvStats {
     pub force: u8,
     pub resistance: u8,
@@ -28,7 +66,10 @@ pub struct IvStats {
     pub force_magique: u8,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Also the shape of stat modifiers, whose left out stats are left as they
+/// are.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
 pub struct Stats {
     pub force: f32,
     pub resistance: f32,
@@ -47,39 +88,61 @@ impl fmt::Display for Stats {
     }
 }
 
+impl Stats {
+    /// Every stat with its name, in the order of the sheet.
+    pub fn fields(&self) -> [(&'static str, f32); 5] {
+        [
+            ("Force", self.force),
+            ("Résistance", self.resistance),
+            ("Vitesse", self.vitesse),
+            ("Force magique", self.force_magique),
+            ("Résistance magique", self.resistance_magique),
+        ]
+    }
+
+    /// Combines each stat with the same stat of `other`.
+    pub fn zip(&self, other: &Stats, f: impl Fn(f32, f32) -> f32) -> Stats {
+        Stats {
+            force: f(self.force, other.force),
+            resistance: f(self.resistance, other.resistance),
+            vitesse: f(self.vitesse, other.vitesse),
+            force_magique: f(self.force_magique, other.force_magique),
+            resistance_magique: f(self.resistance_magique, other.resistance_magique),
+        }
+    }
+}
+
+/// Final stats at `level`. `stats_mod` is added to the race base of each
+/// stat, which is how equipment and injuries weigh on them; see
+/// `modifiers::total`.
 pub fn calc_stats(
     iv: IvStats,
     level: u8,
-    stats_race: StatsRace,
+    stats_race: &StatsRace,
     stats_mod: Option<Stats>,
 ) -> Stats {
-    let stats_mod = stats_mod.unwrap_or(Stats {
-        force: 0.,
-        resistance: 0.,
-        vitesse: 0.,
-        force_magique: 0.,
-        resistance_magique: 0.,
-    });
+    let stats_mod = stats_mod.unwrap_or_default();
     let mut res = Stats {
         force: (((2. * (stats_mod.force + stats_race.force as f32) + iv.force as f32)
             * (level as f32 + 2.))
             / 150.
             + 5.),
-        resistance: ((2. * (stats_mod.force + stats_race.resistance as f32)
+        resistance: ((2. * (stats_mod.resistance + stats_race.resistance as f32)
             + iv.resistance as f32)
             * (level as f32 + 2.))
             / 150.
             + 5.,
-        vitesse: ((2. * (stats_mod.force + stats_race.vitesse as f32) + iv.vitesse as f32)
+        vitesse: ((2. * (stats_mod.vitesse + stats_race.vitesse as f32) + iv.vitesse as f32)
             * (level as f32 + 2.))
             / 150.
             + 5.,
-        force_magique: ((2. * (stats_mod.force + stats_race.force_magique as f32)
+        force_magique: ((2. * (stats_mod.force_magique + stats_race.force_magique as f32)
             + iv.force_magique as f32)
             * (level as f32 + 2.))
             / 150.
             + 5.,
-        resistance_magique: ((2. * (stats_mod.force + stats_race.resistance_magique as f32)
+        resistance_magique: ((2.
+            * (stats_mod.resistance_magique + stats_race.resistance_magique as f32)
             + iv.resistance_magique as f32)
             * (level as f32 + 2.))
             / 150.
@@ -95,25 +158,46 @@ pub fn calc_stats(
     res
 }
 
-pub fn get_race_stats(race: String) -> StatsRace {
-    let path = env::var("STATS_RACE_JSON").expect("Error in the env variable");
-    let file = File::open(path);
-    let reader = BufReader::new(file.unwrap());
-    let data: StatsRaces = serde_json::from_reader(reader).unwrap();
-    let mut res = StatsRace {
-        race: "Erreur".to_string(),
-        force: 0,
-        resistance: 0,
-        vitesse: 0,
-        force_magique: 0,
-        resistance_magique: 0,
-    };
+/// Base stats of every race, checked when loaded so that a missing entry is
+/// reported once at boot rather than as wrong numbers in `?stats`.
+pub struct RaceTable {
+    races: HashMap<Race, StatsRace>,
+}
 
-    for i in data.data {
-        if i.race == race {
-            res = i
+impl RaceTable {
+    /// Reads STATS_RACE_JSON and refuses it unless every form of every
+    /// `Race` has exactly one entry. All problems are listed at once.
+    pub fn load(path: &Path) -> Result<RaceTable> {
+        let data: StatsRaces = read_json(path)?;
+        let mut races = HashMap::new();
+        let mut problems = Vec::new();
+
+        for entry in data.data {
+            match entry.race.parse::<Race>() {
+                Ok(race) if races.contains_key(&race) => {
+                    problems.push(format!("la race {} apparaît plusieurs fois", entry.race))
+                }
+                Ok(race) => {
+                    races.insert(race, entry);
+                }
+                Err(why) => problems.push(why),
+            }
+        }
+        for race in Race::ALL.iter().flat_map(Race::forms) {
+            if !races.contains_key(race) {
+                problems.push(format!("aucune statistique pour la race {}", race.key()));
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(RaceTable { races }),
+            false => Err(Error::invalid_data(path, problems.join("\n"))),
         }
     }
 
-    res
+    pub fn get(&self, race: Race) -> Result<&StatsRace> {
+        self.races
+            .get(&race)
+            .ok_or_else(|| Error::UnknownRace(race.key().to_string()))
+    }
 }
@@ -0,0 +1,55 @@
+use std::path::Path;
+
+use super::{AdventurerRepository, StorageError};
+use crate::adventurer::{Adventurer, Adventurers};
+use crate::data::read_json;
+use crate::error::Result;
+
+pub fn read_adventurers(path: &Path) -> Result<Vec<Adventurer>> {
+    let data: Adventurers = read_json(path)?;
+
+    Ok(data.adventurer)
+}
+
+/// Read-only backend over the historical ADVENTURER_JSON layout.
+pub struct JsonAdventurers {
+    adventurers: Vec<Adventurer>,
+}
+
+impl JsonAdventurers {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(JsonAdventurers {
+            adventurers: read_adventurers(path)?,
+        })
+    }
+}
+
+impl AdventurerRepository for JsonAdventurers {
+    fn all(&self) -> Result<Vec<Adventurer>, StorageError> {
+        Ok(self.adventurers.clone())
+    }
+
+    fn find(&self, name: &str) -> Result<Option<Adventurer>, StorageError> {
+        Ok(self.adventurers.iter().find(|a| a.name == name).cloned())
+    }
+
+    fn insert(&self, _: &Adventurer) -> Result<u32, StorageError> {
+        Err(StorageError::ReadOnly)
+    }
+
+    fn update(&self, _: &Adventurer) -> Result<(), StorageError> {
+        Err(StorageError::ReadOnly)
+    }
+
+    fn modify(
+        &self,
+        _: &str,
+        _: &mut dyn FnMut(&mut Adventurer) -> bool,
+    ) -> Result<bool, StorageError> {
+        Err(StorageError::ReadOnly)
+    }
+
+    fn remove(&self, _: &str) -> Result<bool, StorageError> {
+        Err(StorageError::ReadOnly)
+    }
+}
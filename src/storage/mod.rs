@@ -0,0 +1,150 @@
+use serenity::prelude::*;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::adventurer::Adventurer;
+use crate::encounter::Encounter;
+use crate::rolls::{RollRecord, RollTally, Subject};
+use crate::weather::WeatherEntry;
+
+pub mod json;
+pub mod sqlite;
+
+#[derive(Debug)]
+pub enum StorageError {
+    Sqlite(rusqlite::Error),
+    Corrupted(String),
+    ReadOnly,
+    NotFound(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::Sqlite(why) => write!(f, "erreur SQLite: {}", why),
+            StorageError::Corrupted(why) => write!(f, "données corrompues: {}", why),
+            StorageError::ReadOnly => write!(f, "le stockage des aventuriers est en lecture seule"),
+            StorageError::NotFound(name) => write!(f, "aventurier introuvable: {}", name),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+impl From<rusqlite::Error> for StorageError {
+    fn from(why: rusqlite::Error) -> Self {
+        StorageError::Sqlite(why)
+    }
+}
+
+/// Every command reads and writes adventurers through this trait, so the
+/// backend (SQLite or the legacy JSON file) is chosen once at startup.
+pub trait AdventurerRepository: Send + Sync {
+    fn all(&self) -> Result<Vec<Adventurer>, StorageError>;
+
+    fn find(&self, name: &str) -> Result<Option<Adventurer>, StorageError>;
+
+    /// Stores a new adventurer and returns the id it was given.
+    fn insert(&self, adventurer: &Adventurer) -> Result<u32, StorageError>;
+
+    /// Replaces the stored adventurer with the same id.
+    fn update(&self, adventurer: &Adventurer) -> Result<(), StorageError>;
+
+    /// Applies `change` to the stored adventurer named `name`, and saves it
+    /// when `change` returns true, with no other write in between. Returns
+    /// whether it was saved.
+    fn modify(
+        &self,
+        name: &str,
+        change: &mut dyn FnMut(&mut Adventurer) -> bool,
+    ) -> Result<bool, StorageError>;
+
+    fn remove(&self, name: &str) -> Result<bool, StorageError>;
+}
+
+pub struct AdventurerStore;
+
+impl TypeMapKey for AdventurerStore {
+    type Value = Arc<dyn AdventurerRepository>;
+}
+
+pub async fn adventurers(ctx: &Context) -> Arc<dyn AdventurerRepository> {
+    let data = ctx.data.read().await;
+    data.get::<AdventurerStore>()
+        .expect("AdventurerStore is inserted at startup")
+        .clone()
+}
+
+/// The weather calendar of every palier.
+pub trait WeatherRepository: Send + Sync {
+    /// The weather drawn last for `pallier`, over or not.
+    fn current(&self, pallier: u32) -> Result<Option<WeatherEntry>, StorageError>;
+
+    fn record(&self, entry: &WeatherEntry) -> Result<(), StorageError>;
+
+    /// The last `limit` weathers of `pallier`, most recent first.
+    fn history(&self, pallier: u32, limit: usize) -> Result<Vec<WeatherEntry>, StorageError>;
+}
+
+pub struct WeatherStore;
+
+impl TypeMapKey for WeatherStore {
+    type Value = Arc<dyn WeatherRepository>;
+}
+
+pub async fn weather(ctx: &Context) -> Arc<dyn WeatherRepository> {
+    let data = ctx.data.read().await;
+    data.get::<WeatherStore>()
+        .expect("WeatherStore is inserted at startup")
+        .clone()
+}
+
+/// The encounter running in each channel, at most one per channel.
+pub trait EncounterRepository: Send + Sync {
+    fn find(&self, channel_id: u64) -> Result<Option<Encounter>, StorageError>;
+
+    /// Stores the encounter of its channel, replacing the previous state.
+    fn save(&self, encounter: &Encounter) -> Result<(), StorageError>;
+
+    fn remove(&self, channel_id: u64) -> Result<bool, StorageError>;
+}
+
+pub struct EncounterStore;
+
+impl TypeMapKey for EncounterStore {
+    type Value = Arc<dyn EncounterRepository>;
+}
+
+pub async fn encounters(ctx: &Context) -> Arc<dyn EncounterRepository> {
+    let data = ctx.data.read().await;
+    data.get::<EncounterStore>()
+        .expect("EncounterStore is inserted at startup")
+        .clone()
+}
+
+/// Every exchange resolved by the bot, kept for `?rejouer` and `?historique`.
+pub trait RollRepository: Send + Sync {
+    /// Stores a roll and returns the id it was given.
+    fn record(&self, roll: &RollRecord) -> Result<u32, StorageError>;
+
+    fn find(&self, id: u32) -> Result<Option<RollRecord>, StorageError>;
+
+    /// The `limit` latest rolls `subject` took part in, most recent first.
+    fn history(&self, subject: &Subject, limit: usize) -> Result<Vec<RollRecord>, StorageError>;
+
+    /// Totals over every roll `subject` took part in.
+    fn tally(&self, subject: &Subject) -> Result<RollTally, StorageError>;
+}
+
+pub struct RollStore;
+
+impl TypeMapKey for RollStore {
+    type Value = Arc<dyn RollRepository>;
+}
+
+pub async fn rolls(ctx: &Context) -> Arc<dyn RollRepository> {
+    let data = ctx.data.read().await;
+    data.get::<RollStore>()
+        .expect("RollStore is inserted at startup")
+        .clone()
+}
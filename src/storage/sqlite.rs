@@ -0,0 +1,1040 @@
+use rusqlite::types::{Type, Value};
+use rusqlite::Error::FromSqlConversionFailure;
+use rusqlite::{params, Connection, OptionalExtension, Row, Transaction};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, MutexGuard};
+use tracing::info;
+
+use super::{
+    AdventurerRepository, EncounterRepository, RollRepository, StorageError, WeatherRepository,
+};
+use crate::adventurer::{Adventurer, Energy, Health, Jobs, Magic, Physical};
+use crate::effects::ActiveEffect;
+use crate::encounter::{Combatant, Encounter, Participant};
+use crate::ranks::Promotion;
+use crate::rolls::{RollOutcome, RollRecord, RollTally, Subject};
+use crate::stats::IvStats;
+use crate::weapons::EquippedWeapon;
+use crate::weather::WeatherEntry;
+
+// Each entry is applied once, in order, and recorded in `PRAGMA user_version`.
+// Never edit a migration that has shipped: append a new one instead.
+const MIGRATIONS: &[&str] = &[
+    r#"
+    CREATE TABLE adventurers (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL UNIQUE,
+        race TEXT NOT NULL,
+        rank TEXT NOT NULL,
+        level INTEGER NOT NULL,
+        iv_force INTEGER NOT NULL,
+        iv_resistance INTEGER NOT NULL,
+        iv_vitesse INTEGER NOT NULL,
+        iv_resistance_magique INTEGER NOT NULL,
+        iv_force_magique INTEGER NOT NULL,
+        physical_energy INTEGER NOT NULL,
+        physical_energy_max INTEGER NOT NULL,
+        health_state TEXT NOT NULL,
+        health_description TEXT NOT NULL
+    );
+    CREATE TABLE adventurer_jobs (
+        adventurer_id INTEGER NOT NULL REFERENCES adventurers(id) ON DELETE CASCADE,
+        job TEXT NOT NULL,
+        level INTEGER NOT NULL,
+        PRIMARY KEY (adventurer_id, job)
+    );
+    CREATE TABLE adventurer_magics (
+        adventurer_id INTEGER NOT NULL REFERENCES adventurers(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        actual_energy INTEGER NOT NULL,
+        energy INTEGER NOT NULL,
+        PRIMARY KEY (adventurer_id, position)
+    );
+"#,
+    r#"
+    CREATE TABLE weather_history (
+        id INTEGER PRIMARY KEY,
+        pallier INTEGER NOT NULL,
+        kind TEXT NOT NULL,
+        climat TEXT NOT NULL,
+        started_at INTEGER NOT NULL,
+        ends_at INTEGER NOT NULL
+    );
+    CREATE INDEX weather_history_pallier ON weather_history (pallier, started_at);
+"#,
+    r#"
+    CREATE TABLE adventurer_weapons (
+        adventurer_id INTEGER PRIMARY KEY REFERENCES adventurers(id) ON DELETE CASCADE,
+        name TEXT NOT NULL,
+        durability REAL NOT NULL,
+        max_durability INTEGER NOT NULL
+    );
+"#,
+    r#"
+    CREATE TABLE encounters (
+        channel_id INTEGER PRIMARY KEY,
+        turn INTEGER NOT NULL
+    );
+    CREATE TABLE encounter_participants (
+        channel_id INTEGER NOT NULL REFERENCES encounters(channel_id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        side TEXT NOT NULL,
+        target TEXT,
+        wounds INTEGER NOT NULL,
+        out INTEGER NOT NULL,
+        PRIMARY KEY (channel_id, position)
+    );
+"#,
+    r#"
+    CREATE TABLE rolls (
+        id INTEGER PRIMARY KEY,
+        seed INTEGER NOT NULL,
+        channel_id INTEGER NOT NULL,
+        created_at INTEGER NOT NULL,
+        first TEXT NOT NULL,
+        second TEXT NOT NULL,
+        winner TEXT NOT NULL,
+        inputs TEXT NOT NULL
+    );
+"#,
+    r#"
+    ALTER TABLE rolls ADD COLUMN loser TEXT NOT NULL DEFAULT '';
+    UPDATE rolls SET loser = CASE WHEN winner = first THEN second ELSE first END;
+    ALTER TABLE rolls ADD COLUMN speed_tier TEXT;
+    ALTER TABLE rolls ADD COLUMN attack_tier TEXT;
+    ALTER TABLE rolls ADD COLUMN usure REAL;
+    CREATE INDEX rolls_first ON rolls (first, created_at);
+    CREATE INDEX rolls_second ON rolls (second, created_at);
+"#,
+    r#"
+    CREATE TABLE adventurer_effects (
+        adventurer_id INTEGER NOT NULL REFERENCES adventurers(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        expires_at INTEGER NOT NULL,
+        last_tick INTEGER NOT NULL,
+        PRIMARY KEY (adventurer_id, position)
+    );
+"#,
+    r#"
+    ALTER TABLE adventurers ADD COLUMN xp INTEGER NOT NULL DEFAULT 0;
+"#,
+    r#"
+    ALTER TABLE adventurers ADD COLUMN quests INTEGER NOT NULL DEFAULT 0;
+    CREATE TABLE adventurer_promotions (
+        adventurer_id INTEGER NOT NULL REFERENCES adventurers(id) ON DELETE CASCADE,
+        position INTEGER NOT NULL,
+        from_rank TEXT NOT NULL,
+        to_rank TEXT NOT NULL,
+        approved_by INTEGER NOT NULL,
+        promoted_at INTEGER NOT NULL,
+        PRIMARY KEY (adventurer_id, position)
+    );
+"#,
+    r#"
+    ALTER TABLE adventurer_jobs ADD COLUMN xp INTEGER NOT NULL DEFAULT 0;
+"#,
+    r#"
+    ALTER TABLE encounter_participants ADD COLUMN kind TEXT NOT NULL DEFAULT 'aventurier';
+    ALTER TABLE encounter_participants ADD COLUMN creature TEXT;
+    ALTER TABLE encounter_participants ADD COLUMN level INTEGER;
+    ALTER TABLE encounter_participants ADD COLUMN stats TEXT;
+"#,
+    r#"
+    ALTER TABLE rolls ADD COLUMN first_id INTEGER;
+    ALTER TABLE rolls ADD COLUMN second_id INTEGER;
+    ALTER TABLE rolls ADD COLUMN first_won INTEGER NOT NULL DEFAULT 1;
+    UPDATE rolls SET first_won = (winner = first);
+    UPDATE rolls SET first_id = (SELECT id FROM adventurers WHERE adventurers.name = rolls.first);
+    UPDATE rolls SET second_id = (SELECT id FROM adventurers WHERE adventurers.name = rolls.second);
+    CREATE INDEX rolls_first_id ON rolls (first_id, created_at);
+    CREATE INDEX rolls_second_id ON rolls (second_id, created_at);
+"#,
+];
+
+pub struct Database {
+    conn: Mutex<Connection>,
+}
+
+impl Database {
+    pub fn open(path: &Path) -> Result<Self, StorageError> {
+        Database::init(Connection::open(path)?)
+    }
+
+    /// A database that is lost when the bot stops, for the state that has
+    /// nowhere else to go when adventurers come from the JSON file.
+    pub fn open_in_memory() -> Result<Self, StorageError> {
+        Database::init(Connection::open_in_memory()?)
+    }
+
+    fn init(mut conn: Connection) -> Result<Self, StorageError> {
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+
+        Ok(Database {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    pub fn conn(&self) -> MutexGuard<'_, Connection> {
+        // A panic while holding the lock cannot leave a half-applied write
+        // behind since every write runs in a transaction.
+        self.conn
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// One-shot import of the legacy `Adventurers` JSON layout. Existing
+    /// adventurers with the same id or name are replaced; ids are kept.
+    pub fn import_adventurers(&self, adventurers: &[Adventurer]) -> Result<usize, StorageError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        for adventurer in adventurers {
+            tx.execute(
+                "DELETE FROM adventurers WHERE id = ?1 OR name = ?2",
+                params![adventurer.id, adventurer.name],
+            )?;
+            write_adventurer(&tx, Some(adventurer.id), adventurer)?;
+        }
+        tx.commit()?;
+
+        Ok(adventurers.len())
+    }
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), StorageError> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    if version > MIGRATIONS.len() {
+        return Err(StorageError::Corrupted(format!(
+            "schéma en version {} mais seules {} migrations sont connues",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+    for (i, migration) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", i + 1)?;
+        tx.commit()?;
+        info!("Applied database migration {}", i + 1);
+    }
+
+    Ok(())
+}
+
+const SELECT_ADVENTURER: &str = "SELECT id, name, race, rank, level, iv_force, iv_resistance, \
+    iv_vitesse, iv_resistance_magique, iv_force_magique, physical_energy, physical_energy_max, \
+    health_state, health_description, xp, quests FROM adventurers";
+
+fn parse_column<T>(row: &Row, idx: usize) -> rusqlite::Result<T>
+where
+    T: FromStr,
+    T::Err: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let text: String = row.get(idx)?;
+    text.parse()
+        .map_err(|why: T::Err| FromSqlConversionFailure(idx, Type::Text, why.into()))
+}
+
+fn read_row(row: &Row) -> rusqlite::Result<Adventurer> {
+    Ok(Adventurer {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        race: parse_column(row, 2)?,
+        rank: parse_column(row, 3)?,
+        level: row.get(4)?,
+        xp: row.get(14)?,
+        quests: row.get(15)?,
+        iv: IvStats {
+            force: row.get(5)?,
+            resistance: row.get(6)?,
+            vitesse: row.get(7)?,
+            resistance_magique: row.get(8)?,
+            force_magique: row.get(9)?,
+        },
+        jobs: Jobs::default(),
+        job_xp: BTreeMap::new(),
+        energy: Energy {
+            physical: Physical {
+                actual_energy: row.get(10)?,
+                energy: row.get(11)?,
+            },
+            magical: Vec::new(),
+        },
+        health: Health {
+            state: parse_column(row, 12)?,
+            description: row.get(13)?,
+        },
+        weapon: None,
+        effects: Vec::new(),
+        promotions: Vec::new(),
+    })
+}
+
+fn load(
+    conn: &Connection,
+    filter: &str,
+    name: Option<&str>,
+) -> Result<Vec<Adventurer>, StorageError> {
+    let mut stmt = conn.prepare(&format!("{} {} ORDER BY id", SELECT_ADVENTURER, filter))?;
+    let mut res = match name {
+        Some(name) => stmt
+            .query_map(params![name], read_row)?
+            .collect::<Result<Vec<_>, _>>()?,
+        None => stmt
+            .query_map([], read_row)?
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+
+    let mut jobs =
+        conn.prepare("SELECT job, level, xp FROM adventurer_jobs WHERE adventurer_id = ?1")?;
+    let mut magics = conn.prepare(
+        "SELECT name, actual_energy, energy FROM adventurer_magics \
+         WHERE adventurer_id = ?1 ORDER BY position",
+    )?;
+    let mut weapons = conn.prepare(
+        "SELECT name, durability, max_durability FROM adventurer_weapons \
+         WHERE adventurer_id = ?1",
+    )?;
+    let mut effects = conn.prepare(
+        "SELECT name, expires_at, last_tick FROM adventurer_effects \
+         WHERE adventurer_id = ?1 ORDER BY position",
+    )?;
+    let mut promotions = conn.prepare(
+        "SELECT from_rank, to_rank, approved_by, promoted_at FROM adventurer_promotions \
+         WHERE adventurer_id = ?1 ORDER BY position",
+    )?;
+    for adventurer in res.iter_mut() {
+        let rows = jobs.query_map(params![adventurer.id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u8>(1)?,
+                row.get::<_, u32>(2)?,
+            ))
+        })?;
+        for job in rows {
+            let (job, level, xp) = job?;
+            match adventurer.jobs.level_mut(&job) {
+                Some(slot) => *slot = level,
+                None => return Err(StorageError::Corrupted(format!("métier inconnu: {}", job))),
+            }
+            if xp > 0 {
+                adventurer.job_xp.insert(job, xp);
+            }
+        }
+
+        adventurer.energy.magical = magics
+            .query_map(params![adventurer.id], |row| {
+                Ok(Magic {
+                    name: row.get(0)?,
+                    actual_energy: row.get(1)?,
+                    energy: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        adventurer.weapon = weapons
+            .query_row(params![adventurer.id], |row| {
+                Ok(EquippedWeapon {
+                    name: row.get(0)?,
+                    durability: row.get(1)?,
+                    max_durability: row.get(2)?,
+                })
+            })
+            .optional()?;
+
+        adventurer.effects = effects
+            .query_map(params![adventurer.id], |row| {
+                Ok(ActiveEffect {
+                    name: row.get(0)?,
+                    expires_at: row.get(1)?,
+                    last_tick: row.get(2)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        adventurer.promotions = promotions
+            .query_map(params![adventurer.id], |row| {
+                Ok(Promotion {
+                    from: parse_column(row, 0)?,
+                    to: parse_column(row, 1)?,
+                    approved_by: row.get::<_, i64>(2)? as u64,
+                    promoted_at: row.get(3)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+    }
+
+    Ok(res)
+}
+
+fn write_adventurer(
+    tx: &Transaction,
+    id: Option<u32>,
+    adventurer: &Adventurer,
+) -> Result<u32, StorageError> {
+    tx.execute(
+        "INSERT INTO adventurers (id, name, race, rank, level, iv_force, iv_resistance, \
+         iv_vitesse, iv_resistance_magique, iv_force_magique, physical_energy, \
+         physical_energy_max, health_state, health_description, xp, quests) \
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+        params![
+            id,
+            adventurer.name,
+            adventurer.race.key(),
+            adventurer.rank.to_string(),
+            adventurer.level,
+            adventurer.iv.force,
+            adventurer.iv.resistance,
+            adventurer.iv.vitesse,
+            adventurer.iv.resistance_magique,
+            adventurer.iv.force_magique,
+            adventurer.energy.physical.actual_energy,
+            adventurer.energy.physical.energy,
+            adventurer.health.state.key(),
+            adventurer.health.description,
+            adventurer.xp,
+            adventurer.quests,
+        ],
+    )?;
+    let id = tx.last_insert_rowid() as u32;
+
+    for (job, level) in adventurer.jobs.levels() {
+        tx.execute(
+            "INSERT INTO adventurer_jobs (adventurer_id, job, level, xp) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                id,
+                job,
+                level,
+                adventurer.job_xp.get(job).copied().unwrap_or(0)
+            ],
+        )?;
+    }
+    for (position, magic) in adventurer.energy.magical.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO adventurer_magics (adventurer_id, position, name, actual_energy, energy) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, position, magic.name, magic.actual_energy, magic.energy],
+        )?;
+    }
+    if let Some(weapon) = &adventurer.weapon {
+        tx.execute(
+            "INSERT INTO adventurer_weapons (adventurer_id, name, durability, max_durability) \
+             VALUES (?1, ?2, ?3, ?4)",
+            params![id, weapon.name, weapon.durability, weapon.max_durability],
+        )?;
+    }
+    for (position, effect) in adventurer.effects.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO adventurer_effects (adventurer_id, position, name, expires_at, last_tick) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![id, position, effect.name, effect.expires_at, effect.last_tick],
+        )?;
+    }
+    for (position, promotion) in adventurer.promotions.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO adventurer_promotions \
+             (adventurer_id, position, from_rank, to_rank, approved_by, promoted_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                id,
+                position,
+                promotion.from.to_string(),
+                promotion.to.to_string(),
+                promotion.approved_by as i64,
+                promotion.promoted_at
+            ],
+        )?;
+    }
+
+    Ok(id)
+}
+
+impl AdventurerRepository for Database {
+    fn all(&self) -> Result<Vec<Adventurer>, StorageError> {
+        load(&self.conn(), "", None)
+    }
+
+    fn find(&self, name: &str) -> Result<Option<Adventurer>, StorageError> {
+        Ok(load(&self.conn(), "WHERE name = ?1", Some(name))?.pop())
+    }
+
+    fn insert(&self, adventurer: &Adventurer) -> Result<u32, StorageError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let id = write_adventurer(&tx, None, adventurer)?;
+        tx.commit()?;
+
+        Ok(id)
+    }
+
+    fn update(&self, adventurer: &Adventurer) -> Result<(), StorageError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let exists = tx
+            .query_row(
+                "SELECT 1 FROM adventurers WHERE id = ?1",
+                params![adventurer.id],
+                |_| Ok(()),
+            )
+            .optional()?;
+        if exists.is_none() {
+            return Err(StorageError::NotFound(adventurer.name.clone()));
+        }
+        tx.execute(
+            "DELETE FROM adventurers WHERE id = ?1",
+            params![adventurer.id],
+        )?;
+        write_adventurer(&tx, Some(adventurer.id), adventurer)?;
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn modify(
+        &self,
+        name: &str,
+        change: &mut dyn FnMut(&mut Adventurer) -> bool,
+    ) -> Result<bool, StorageError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let mut adventurer = match load(&tx, "WHERE name = ?1", Some(name))?.pop() {
+            Some(adventurer) => adventurer,
+            None => return Ok(false),
+        };
+        if !change(&mut adventurer) {
+            return Ok(false);
+        }
+        tx.execute(
+            "DELETE FROM adventurers WHERE id = ?1",
+            params![adventurer.id],
+        )?;
+        write_adventurer(&tx, Some(adventurer.id), &adventurer)?;
+        tx.commit()?;
+
+        Ok(true)
+    }
+
+    fn remove(&self, name: &str) -> Result<bool, StorageError> {
+        let removed = self
+            .conn()
+            .execute("DELETE FROM adventurers WHERE name = ?1", params![name])?;
+
+        Ok(removed > 0)
+    }
+}
+
+const SELECT_WEATHER: &str =
+    "SELECT pallier, kind, climat, started_at, ends_at FROM weather_history \
+     WHERE pallier = ?1 ORDER BY started_at DESC, id DESC LIMIT ?2";
+
+fn read_weather(row: &Row) -> rusqlite::Result<WeatherEntry> {
+    Ok(WeatherEntry {
+        pallier: row.get(0)?,
+        kind: parse_column(row, 1)?,
+        climat: row.get(2)?,
+        started_at: row.get(3)?,
+        ends_at: row.get(4)?,
+    })
+}
+
+impl WeatherRepository for Database {
+    fn current(&self, pallier: u32) -> Result<Option<WeatherEntry>, StorageError> {
+        Ok(WeatherRepository::history(self, pallier, 1)?.pop())
+    }
+
+    fn record(&self, entry: &WeatherEntry) -> Result<(), StorageError> {
+        self.conn().execute(
+            "INSERT INTO weather_history (pallier, kind, climat, started_at, ends_at) \
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                entry.pallier,
+                entry.kind.key(),
+                entry.climat,
+                entry.started_at,
+                entry.ends_at
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    fn history(&self, pallier: u32, limit: usize) -> Result<Vec<WeatherEntry>, StorageError> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(SELECT_WEATHER)?;
+        let res = stmt
+            .query_map(params![pallier, limit], read_weather)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(res)
+    }
+}
+
+// Discord ids fit in 63 bits, SQLite integers are signed.
+fn channel_key(channel_id: u64) -> i64 {
+    channel_id as i64
+}
+
+// Columns 5 to 8 of an `encounter_participants` row.
+fn read_combatant(row: &Row) -> rusqlite::Result<Combatant> {
+    let kind: String = row.get(5)?;
+    match kind.as_str() {
+        "creature" => Ok(Combatant::Creature {
+            creature: row.get(6)?,
+            level: row.get(7)?,
+        }),
+        "improvise" => {
+            let stats: String = row.get(8)?;
+            Ok(Combatant::Improvised {
+                level: row.get(7)?,
+                stats: serde_json::from_str(&stats)
+                    .map_err(|why| FromSqlConversionFailure(8, Type::Text, why.into()))?,
+            })
+        }
+        _ => Ok(Combatant::Adventurer),
+    }
+}
+
+impl EncounterRepository for Database {
+    fn find(&self, channel_id: u64) -> Result<Option<Encounter>, StorageError> {
+        let conn = self.conn();
+        let turn = conn
+            .query_row(
+                "SELECT turn FROM encounters WHERE channel_id = ?1",
+                params![channel_key(channel_id)],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let turn = match turn {
+            Some(turn) => turn,
+            None => return Ok(None),
+        };
+
+        let mut stmt = conn.prepare(
+            "SELECT name, side, target, wounds, out, kind, creature, level, stats \
+             FROM encounter_participants WHERE channel_id = ?1 ORDER BY position",
+        )?;
+        let participants = stmt
+            .query_map(params![channel_key(channel_id)], |row| {
+                Ok(Participant {
+                    name: row.get(0)?,
+                    combatant: read_combatant(row)?,
+                    side: row.get(1)?,
+                    target: row.get(2)?,
+                    wounds: row.get(3)?,
+                    out: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Some(Encounter {
+            channel_id,
+            turn,
+            participants,
+        }))
+    }
+
+    fn save(&self, encounter: &Encounter) -> Result<(), StorageError> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let channel_id = channel_key(encounter.channel_id);
+        tx.execute(
+            "DELETE FROM encounters WHERE channel_id = ?1",
+            params![channel_id],
+        )?;
+        tx.execute(
+            "INSERT INTO encounters (channel_id, turn) VALUES (?1, ?2)",
+            params![channel_id, encounter.turn],
+        )?;
+        for (position, participant) in encounter.participants.iter().enumerate() {
+            let (kind, creature, level, stats) = match &participant.combatant {
+                Combatant::Adventurer => ("aventurier", None, None, None),
+                Combatant::Creature { creature, level } => {
+                    ("creature", Some(creature.as_str()), Some(*level), None)
+                }
+                Combatant::Improvised { level, stats } => (
+                    "improvise",
+                    None,
+                    Some(*level),
+                    Some(
+                        serde_json::to_string(stats)
+                            .map_err(|why| rusqlite::Error::ToSqlConversionFailure(why.into()))?,
+                    ),
+                ),
+            };
+            tx.execute(
+                "INSERT INTO encounter_participants \
+                 (channel_id, position, name, side, target, wounds, out, \
+                 kind, creature, level, stats) \
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                params![
+                    channel_id,
+                    position,
+                    participant.name,
+                    participant.side,
+                    participant.target,
+                    participant.wounds,
+                    participant.out,
+                    kind,
+                    creature,
+                    level,
+                    stats
+                ],
+            )?;
+        }
+        tx.commit()?;
+
+        Ok(())
+    }
+
+    fn remove(&self, channel_id: u64) -> Result<bool, StorageError> {
+        let removed = self.conn().execute(
+            "DELETE FROM encounters WHERE channel_id = ?1",
+            params![channel_key(channel_id)],
+        )?;
+
+        Ok(removed > 0)
+    }
+}
+
+const SELECT_ROLL: &str = "SELECT id, seed, channel_id, created_at, first, second, winner, \
+    loser, speed_tier, attack_tier, usure, inputs, first_id, second_id, first_won FROM rolls";
+
+fn read_roll(row: &Row) -> rusqlite::Result<RollRecord> {
+    let speed_tier: Option<String> = row.get(8)?;
+    let attack_tier: Option<String> = row.get(9)?;
+    let usure: Option<f32> = row.get(10)?;
+    Ok(RollRecord {
+        id: row.get(0)?,
+        seed: row.get::<_, i64>(1)? as u64,
+        channel_id: row.get::<_, i64>(2)? as u64,
+        created_at: row.get(3)?,
+        first: row.get(4)?,
+        second: row.get(5)?,
+        first_id: row.get(12)?,
+        second_id: row.get(13)?,
+        winner: row.get(6)?,
+        loser: row.get(7)?,
+        first_won: row.get(14)?,
+        outcome: match (speed_tier, attack_tier, usure) {
+            (Some(speed_tier), Some(attack_tier), Some(usure)) => Some(RollOutcome {
+                speed_tier,
+                attack_tier,
+                usure,
+            }),
+            _ => None,
+        },
+        inputs: row.get(11)?,
+    })
+}
+
+impl RollRepository for Database {
+    fn record(&self, roll: &RollRecord) -> Result<u32, StorageError> {
+        let conn = self.conn();
+        let outcome = roll.outcome.as_ref();
+        // Seeds use all 64 bits and are stored as they are, sign aside.
+        conn.execute(
+            "INSERT INTO rolls (seed, channel_id, created_at, first, second, winner, loser, \
+             speed_tier, attack_tier, usure, inputs, first_id, second_id, first_won) \
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![
+                roll.seed as i64,
+                channel_key(roll.channel_id),
+                roll.created_at,
+                roll.first,
+                roll.second,
+                roll.winner,
+                roll.loser,
+                outcome.map(|o| &o.speed_tier),
+                outcome.map(|o| &o.attack_tier),
+                outcome.map(|o| o.usure),
+                roll.inputs,
+                roll.first_id,
+                roll.second_id,
+                roll.first_won
+            ],
+        )?;
+
+        Ok(conn.last_insert_rowid() as u32)
+    }
+
+    fn find(&self, id: u32) -> Result<Option<RollRecord>, StorageError> {
+        let roll = self
+            .conn()
+            .query_row(
+                &format!("{} WHERE id = ?1", SELECT_ROLL),
+                params![id],
+                read_roll,
+            )
+            .optional()?;
+
+        Ok(roll)
+    }
+
+    fn history(&self, subject: &Subject, limit: usize) -> Result<Vec<RollRecord>, StorageError> {
+        let (filter, _, key) = subject_filter(subject);
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "{} WHERE {} ORDER BY created_at DESC, id DESC LIMIT ?2",
+            SELECT_ROLL, filter
+        ))?;
+        let res = stmt
+            .query_map(params![key, limit as i64], read_roll)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(res)
+    }
+
+    fn tally(&self, subject: &Subject) -> Result<RollTally, StorageError> {
+        let (filter, first, key) = subject_filter(subject);
+        let conn = self.conn();
+        let mut stmt = conn.prepare(&format!(
+            "SELECT ({}) = first_won, attack_tier, COUNT(*) FROM rolls WHERE {} GROUP BY 1, 2",
+            first, filter
+        ))?;
+        let mut rows = stmt.query(params![key])?;
+        let mut tally = RollTally::default();
+        while let Some(row) = rows.next()? {
+            let won: bool = row.get(0)?;
+            let tier: Option<String> = row.get(1)?;
+            let count: u32 = row.get(2)?;
+            let tiers = if won {
+                tally.wins += count;
+                &mut tally.dealt
+            } else {
+                tally.losses += count;
+                &mut tally.taken
+            };
+            if let Some(tier) = tier {
+                tiers.push((tier, count));
+            }
+        }
+
+        Ok(tally)
+    }
+}
+
+/// The condition matching the rolls of `subject`, the expression telling
+/// whether they fought on the first side, and the value bound to `?1` in
+/// both. Mirrors `RollRecord::is_first`.
+fn subject_filter(subject: &Subject) -> (&'static str, &'static str, Value) {
+    match subject {
+        Subject::Adventurer(id) => (
+            "(first_id = ?1 OR second_id = ?1)",
+            "first_id IS ?1",
+            Value::Integer(*id as i64),
+        ),
+        Subject::Named(name) => (
+            "((first = ?1 AND first_id IS NULL) OR (second = ?1 AND second_id IS NULL))",
+            "first = ?1 AND first_id IS NULL",
+            Value::Text(name.clone()),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adventurer::{HealthState, Rank};
+    use crate::stats::Stats;
+
+    fn version(db: &Database) -> usize {
+        db.conn()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap()
+    }
+
+    fn adventurer() -> Adventurer {
+        let mut adventurer = Adventurer::new("Arthur", "Humain", "E", Some("12")).unwrap();
+        adventurer.xp = 40;
+        adventurer.quests = 3;
+        *adventurer.jobs.level_mut("forgeron").unwrap() = 2;
+        adventurer.job_xp.insert("forgeron".to_string(), 25);
+        adventurer.energy.magical.push(Magic {
+            name: "Feu".to_string(),
+            actual_energy: 3,
+            energy: 5,
+        });
+        adventurer.health.state = HealthState::Important;
+        adventurer.weapon = Some(EquippedWeapon {
+            name: "Épée longue".to_string(),
+            durability: 42.5,
+            max_durability: 100,
+        });
+        adventurer.effects.push(ActiveEffect {
+            name: "Poison".to_string(),
+            expires_at: 2000,
+            last_tick: 1000,
+        });
+        adventurer.promotions.push(Promotion {
+            from: Rank::F,
+            to: Rank::E,
+            approved_by: 1234,
+            promoted_at: 500,
+        });
+        adventurer
+    }
+
+    #[test]
+    fn migrations_run_from_an_empty_database() {
+        let db = Database::open_in_memory().unwrap();
+        assert_eq!(version(&db), MIGRATIONS.len());
+
+        // Running them again has nothing left to apply.
+        migrate(&mut db.conn()).unwrap();
+        assert_eq!(version(&db), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn newer_schemas_are_refused() {
+        let db = Database::open_in_memory().unwrap();
+        let mut conn = db.conn();
+        conn.pragma_update(None, "user_version", MIGRATIONS.len() + 1)
+            .unwrap();
+        assert!(matches!(
+            migrate(&mut conn),
+            Err(StorageError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn adventurers_round_trip() {
+        let db = Database::open_in_memory().unwrap();
+        let id = db.insert(&adventurer()).unwrap();
+        let stored = AdventurerRepository::find(&db, "Arthur").unwrap().unwrap();
+        assert_eq!(stored.id, id);
+        assert_eq!((stored.rank, stored.level), (Rank::E, 12));
+        assert_eq!((stored.xp, stored.quests), (40, 3));
+        assert_eq!(stored.jobs.level("forgeron"), Some(2));
+        assert_eq!(stored.job_xp.get("forgeron"), Some(&25));
+        assert_eq!(stored.energy.magical[0].name, "Feu");
+        assert_eq!(stored.health.state, HealthState::Important);
+        assert_eq!(stored.weapon.unwrap().durability, 42.5);
+        assert_eq!(stored.effects, adventurer().effects);
+        assert_eq!(stored.promotions[0].approved_by, 1234);
+        assert_eq!(stored.promotions[0].to, Rank::E);
+    }
+
+    #[test]
+    fn modify_saves_only_what_changed() {
+        let db = Database::open_in_memory().unwrap();
+        db.insert(&adventurer()).unwrap();
+        assert!(!db.modify("Arthur", &mut |_| false).unwrap());
+        assert!(!db.modify("Lyra", &mut |_| true).unwrap());
+        let saved = db
+            .modify("Arthur", &mut |adventurer| {
+                adventurer.effects.clear();
+                true
+            })
+            .unwrap();
+        assert!(saved);
+        let stored = AdventurerRepository::find(&db, "Arthur").unwrap().unwrap();
+        assert!(stored.effects.is_empty());
+        assert_eq!(stored.promotions.len(), 1);
+    }
+
+    #[test]
+    fn encounters_keep_what_each_participant_fights_as() {
+        let db = Database::open_in_memory().unwrap();
+        let mut encounter = Encounter::new(42);
+        let combatants = [
+            ("Arthur", Combatant::Adventurer),
+            (
+                "Gobelin2",
+                Combatant::Creature {
+                    creature: "Gobelin".to_string(),
+                    level: 4,
+                },
+            ),
+            (
+                "Brigand",
+                Combatant::Improvised {
+                    level: 3,
+                    stats: Stats {
+                        force: 12.5,
+                        ..Default::default()
+                    },
+                },
+            ),
+        ];
+        for (name, combatant) in combatants.clone() {
+            encounter.add(name, combatant, "camp", 0).unwrap();
+        }
+        db.save(&encounter).unwrap();
+
+        let stored = EncounterRepository::find(&db, 42).unwrap().unwrap();
+        let stored: Vec<(&str, Combatant)> = stored
+            .participants
+            .iter()
+            .map(|p| (p.name.as_str(), p.combatant.clone()))
+            .collect();
+        assert_eq!(stored, combatants);
+    }
+
+    fn roll(
+        first: (&str, Option<u32>),
+        second: (&str, Option<u32>),
+        first_won: bool,
+    ) -> RollRecord {
+        let (winner, loser) = match first_won {
+            true => (first.0, second.0),
+            false => (second.0, first.0),
+        };
+        RollRecord {
+            id: 0,
+            seed: u64::MAX,
+            channel_id: 42,
+            created_at: 0,
+            first: first.0.to_string(),
+            second: second.0.to_string(),
+            first_id: first.1,
+            second_id: second.1,
+            winner: winner.to_string(),
+            loser: loser.to_string(),
+            first_won,
+            outcome: Some(RollOutcome {
+                speed_tier: "faveur".to_string(),
+                attack_tier: "avantage".to_string(),
+                usure: 1.5,
+            }),
+            inputs: String::new(),
+        }
+    }
+
+    #[test]
+    fn roll_history_follows_sheets_and_sides() {
+        let db = Database::open_in_memory().unwrap();
+        // Arthur fights a brigand improvised under his own name, then is
+        // renamed before fighting another.
+        let first =
+            RollRepository::record(&db, &roll(("Arthur", Some(1)), ("Arthur", None), false))
+                .unwrap();
+        RollRepository::record(&db, &roll(("Brigand", None), ("Arthur II", Some(1)), false))
+            .unwrap();
+        let mut old = roll(("Arthur", Some(1)), ("Brigand", None), true);
+        old.outcome = None;
+        RollRepository::record(&db, &old).unwrap();
+
+        let arthur = Subject::Adventurer(1);
+        let tally = db.tally(&arthur).unwrap();
+        assert_eq!((tally.wins, tally.losses), (2, 1));
+        assert_eq!(tally.dealt, [("avantage".to_string(), 1)]);
+        assert_eq!(tally.taken, [("avantage".to_string(), 1)]);
+
+        let brigand = Subject::Named("Arthur".to_string());
+        let tally = db.tally(&brigand).unwrap();
+        assert_eq!((tally.wins, tally.losses), (1, 0));
+
+        let history = RollRepository::history(&db, &arthur, 2).unwrap();
+        assert_eq!(history.len(), 2);
+        assert!(history.iter().all(|r| r.id != first));
+        let history = RollRepository::history(&db, &brigand, 10).unwrap();
+        assert_eq!(history.len(), 1);
+        assert!(history[0].won(&brigand));
+        assert!(!history[0].is_first(&brigand));
+    }
+}
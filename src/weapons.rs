@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::data::read_json;
+use crate::error::{Error, Result};
+use crate::names::{fold, suggestions};
+use crate::stats::Stats;
+
+/// Percentages of durability under which a worn weapon is reported, from
+/// the highest. Reaching zero is reported as a broken weapon.
+pub const WEAR_WARNINGS: [u8; 3] = [50, 25, 10];
+
+// Forgeron levels a repair needs on top of the class's own for a weapon
+// that is broken rather than worn.
+const BROKEN_REPAIR_LEVELS: u8 = 2;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Copy)]
+pub enum WeaponClass {
+    TresLeger,
+    Leger,
+    Moyen,
+    Lourd,
+}
+
+impl fmt::Display for WeaponClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeaponClass::TresLeger => write!(f, "très légère"),
+            WeaponClass::Leger => write!(f, "légère"),
+            WeaponClass::Moyen => write!(f, "moyenne"),
+            WeaponClass::Lourd => write!(f, "lourde"),
+        }
+    }
+}
+
+impl WeaponClass {
+    /// Forgeron level needed to repair a weapon of this class.
+    pub fn repair_level(&self) -> u8 {
+        match self {
+            WeaponClass::TresLeger | WeaponClass::Leger => 1,
+            WeaponClass::Moyen => 2,
+            WeaponClass::Lourd => 3,
+        }
+    }
+}
+
+/// One entry of WEAPONS_JSON. The stats of whoever wields it are multiplied
+/// by `force`, `vitesse` and `force_magique` in combat, after its
+/// `modificateurs` were added to their race base like any other modifier.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Weapon {
+    pub name: String,
+    pub class: WeaponClass,
+    pub force: f32,
+    pub vitesse: f32,
+    #[serde(default = "neutral")]
+    pub force_magique: f32,
+    pub durabilite: u16,
+    #[serde(default)]
+    pub modificateurs: Stats,
+    #[serde(default)]
+    pub traits: Vec<String>,
+}
+
+fn neutral() -> f32 {
+    1.
+}
+
+/// What happened to a weapon that lost durability.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Wear {
+    Intact,
+    /// Fell under one of `WEAR_WARNINGS`, given in percent.
+    Worn(u8),
+    Broken,
+}
+
+/// The weapon an adventurer carries. It starts with the `durabilite` of its
+/// catalogue entry and loses the wear of every exchange its bearer loses.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EquippedWeapon {
+    pub name: String,
+    pub durability: f32,
+    pub max_durability: u16,
+}
+
+impl fmt::Display for EquippedWeapon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}/{})",
+            self.name, self.durability, self.max_durability
+        )?;
+        if self.is_broken() {
+            write!(f, " brisée")?;
+        }
+        Ok(())
+    }
+}
+
+impl EquippedWeapon {
+    pub fn new(weapon: &Weapon) -> Self {
+        EquippedWeapon {
+            name: weapon.name.clone(),
+            durability: weapon.durabilite as f32,
+            max_durability: weapon.durabilite,
+        }
+    }
+
+    /// A broken weapon cannot be fought with until it is repaired.
+    pub fn is_broken(&self) -> bool {
+        self.durability <= 0.
+    }
+
+    fn percent(&self) -> f32 {
+        self.durability * 100. / self.max_durability as f32
+    }
+
+    /// Takes `usure` off the durability and reports the lowest warning
+    /// threshold crossed by doing so.
+    pub fn wear(&mut self, usure: f32) -> Wear {
+        if usure <= 0. || self.is_broken() {
+            return Wear::Intact;
+        }
+        let before = self.percent();
+        self.durability = (self.durability - usure).max(0.);
+        if self.is_broken() {
+            return Wear::Broken;
+        }
+        let after = self.percent();
+
+        WEAR_WARNINGS
+            .iter()
+            .rev()
+            .find(|threshold| after < **threshold as f32 && before >= **threshold as f32)
+            .map_or(Wear::Intact, |threshold| Wear::Worn(*threshold))
+    }
+
+    /// Forgeron level needed to repair this weapon of class `class`.
+    pub fn repair_level(&self, class: WeaponClass) -> u8 {
+        match self.is_broken() {
+            true => class.repair_level() + BROKEN_REPAIR_LEVELS,
+            false => class.repair_level(),
+        }
+    }
+
+    /// A forgeron gives back a tenth of the maximum durability per level.
+    /// Returns the durability restored.
+    pub fn repair(&mut self, forgeron_level: u8) -> f32 {
+        let before = self.durability;
+        let restored = self.max_durability as f32 * forgeron_level as f32 / 10.;
+        self.durability = (self.durability + restored).min(self.max_durability as f32);
+
+        self.durability - before
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct WeaponCatalogue {
+    pub weapons: Vec<Weapon>,
+}
+
+impl WeaponCatalogue {
+    /// Reads WEAPONS_JSON, refusing duplicated names, multipliers that are
+    /// not positive and weapons that would be born broken.
+    pub fn load(path: &Path) -> Result<WeaponCatalogue> {
+        let catalogue: WeaponCatalogue = read_json(path)?;
+        let mut names = HashSet::new();
+        let mut problems = Vec::new();
+
+        for weapon in &catalogue.weapons {
+            if !names.insert(fold(&weapon.name)) {
+                problems.push(format!("l'arme {} apparaît plusieurs fois", weapon.name));
+            }
+            if [weapon.force, weapon.vitesse, weapon.force_magique]
+                .iter()
+                .any(|m| *m <= 0.)
+            {
+                problems.push(format!(
+                    "les multiplicateurs de {} doivent être positifs",
+                    weapon.name
+                ));
+            }
+            if weapon.durabilite == 0 {
+                problems.push(format!("{} n'a aucune durabilité", weapon.name));
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(catalogue),
+            false => Err(Error::invalid_data(path, problems.join("\n"))),
+        }
+    }
+
+    /// Names match regardless of case and accents, so "baton" is "Bâton".
+    pub fn find(&self, name: &str) -> Option<&Weapon> {
+        let name = fold(name);
+        self.weapons.iter().find(|w| fold(&w.name) == name)
+    }
+
+    /// Like `find`, but an unknown name is an error listing the closest
+    /// names of the catalogue.
+    pub fn get(&self, name: &str) -> Result<&Weapon> {
+        self.find(name).ok_or_else(|| Error::UnknownWeapon {
+            name: name.to_string(),
+            suggestions: suggestions(name, self.weapons.iter().map(|w| w.name.as_str())),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weapon(durability: f32) -> EquippedWeapon {
+        EquippedWeapon {
+            name: "Épée longue".to_string(),
+            durability,
+            max_durability: 100,
+        }
+    }
+
+    #[test]
+    fn wear_warns_once_per_threshold_crossed() {
+        let mut equipped = weapon(100.);
+        assert_eq!(equipped.wear(50.), Wear::Intact);
+        // Warnings are for falling under a threshold, not reaching it.
+        assert_eq!(equipped.wear(0.5), Wear::Worn(50));
+        assert_eq!(equipped.wear(0.5), Wear::Intact);
+        assert_eq!(equipped.wear(25.), Wear::Worn(25));
+        assert_eq!(equipped.wear(14.5), Wear::Worn(10));
+        assert_eq!(equipped.wear(9.), Wear::Intact);
+        assert_eq!(equipped.wear(1.), Wear::Broken);
+        assert!(equipped.is_broken());
+    }
+
+    #[test]
+    fn wear_reports_the_lowest_threshold_crossed() {
+        let mut equipped = weapon(60.);
+        assert_eq!(equipped.wear(55.), Wear::Worn(10));
+    }
+
+    #[test]
+    fn wear_stops_at_zero() {
+        let mut equipped = weapon(5.);
+        assert_eq!(equipped.wear(30.), Wear::Broken);
+        assert_eq!(equipped.durability, 0.);
+        assert_eq!(equipped.wear(1.), Wear::Intact);
+        assert_eq!(equipped.durability, 0.);
+    }
+
+    #[test]
+    fn wear_ignores_no_wear() {
+        let mut equipped = weapon(51.);
+        assert_eq!(equipped.wear(0.), Wear::Intact);
+        assert_eq!(equipped.wear(-3.), Wear::Intact);
+        assert_eq!(equipped.durability, 51.);
+    }
+}
@@ -0,0 +1,190 @@
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::config::WeatherConfig;
+use crate::data::read_json;
+use crate::error::{Error, Result};
+use crate::storage::WeatherRepository;
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Meteos {
+    pub palliers: Vec<Pallier>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pallier {
+    pub banner_url: String,
+    pub temperies: Vec<Climat>,
+    pub intemperies: Vec<Climat>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Climat {
+    pub name: String,
+    pub description: String,
+    pub image_url: String,
+    pub temperature: u8,
+    pub humidite: u8,
+    pub duree: u8,
+    pub protection: u8,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ClimatKind {
+    Temperie,
+    Intemperie,
+}
+
+impl ClimatKind {
+    pub fn key(&self) -> &'static str {
+        match self {
+            ClimatKind::Temperie => "Temperie",
+            ClimatKind::Intemperie => "Intemperie",
+        }
+    }
+}
+
+impl fmt::Display for ClimatKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClimatKind::Temperie => write!(f, "tempérie"),
+            ClimatKind::Intemperie => write!(f, "intempérie"),
+        }
+    }
+}
+
+impl FromStr for ClimatKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "Temperie" => Ok(ClimatKind::Temperie),
+            "Intemperie" => Ok(ClimatKind::Intemperie),
+            _ => Err(format!("type de climat inconnu: {}", s)),
+        }
+    }
+}
+
+impl Pallier {
+    /// Draws an intempérie with the given odds, a tempérie otherwise. A
+    /// palier without intempéries always gets a tempérie.
+    pub fn draw<R: Rng>(&self, rng: &mut R, intemperie_chance: f64) -> (ClimatKind, &Climat) {
+        let kind = match self.intemperies.is_empty() || !rng.gen_bool(intemperie_chance) {
+            true => ClimatKind::Temperie,
+            false => ClimatKind::Intemperie,
+        };
+        let climat = self
+            .climats(kind)
+            .choose(rng)
+            .expect("every palier has at least one tempérie");
+
+        (kind, climat)
+    }
+
+    pub fn climats(&self, kind: ClimatKind) -> &[Climat] {
+        match kind {
+            ClimatKind::Temperie => &self.temperies,
+            ClimatKind::Intemperie => &self.intemperies,
+        }
+    }
+
+    pub fn climat(&self, kind: ClimatKind, name: &str) -> Option<&Climat> {
+        self.climats(kind).iter().find(|c| c.name == name)
+    }
+}
+
+impl Meteos {
+    /// The palier numbered from 1, as players call them.
+    pub fn pallier(&self, number: usize) -> Option<&Pallier> {
+        number.checked_sub(1).and_then(|i| self.palliers.get(i))
+    }
+
+    /// Reads METEO_JSON, refusing a file without paliers or with a palier
+    /// that has no tempérie to fall back on.
+    pub fn load(path: &Path) -> Result<Meteos> {
+        let meteos: Meteos = read_json(path)?;
+        if meteos.palliers.is_empty() {
+            return Err(Error::invalid_data(path, "aucun palier n'est défini"));
+        }
+        let problems: Vec<String> = meteos
+            .palliers
+            .iter()
+            .enumerate()
+            .filter(|(_, pallier)| pallier.temperies.is_empty())
+            .map(|(i, _)| format!("le palier {} n'a aucune tempérie", i + 1))
+            .collect();
+
+        match problems.is_empty() {
+            true => Ok(meteos),
+            false => Err(Error::invalid_data(path, problems.join("\n"))),
+        }
+    }
+}
+
+/// One weather of the calendar, from the moment it was drawn until the
+/// moment it gives way to the next one. Times are Unix timestamps.
+#[derive(Debug, Clone)]
+pub struct WeatherEntry {
+    pub pallier: u32,
+    pub kind: ClimatKind,
+    pub climat: String,
+    pub started_at: i64,
+    pub ends_at: i64,
+}
+
+/// The weather a palier has after `rotate`, and whether it was just drawn.
+pub struct Forecast<'a> {
+    pub entry: WeatherEntry,
+    pub climat: &'a Climat,
+    pub changed: bool,
+}
+
+/// Keeps the weather of palier `number` while it lasts and draws the next
+/// one once it is over. A weather whose climat was removed from METEO_JSON
+/// is over as well.
+pub fn rotate<'a, R: Rng>(
+    calendar: &dyn WeatherRepository,
+    meteos: &'a Meteos,
+    number: usize,
+    settings: &WeatherConfig,
+    now: i64,
+    rng: &mut R,
+) -> Result<Option<Forecast<'a>>> {
+    let pallier = match meteos.pallier(number) {
+        Some(pallier) => pallier,
+        None => return Ok(None),
+    };
+    let current = calendar.current(number as u32)?.and_then(|entry| {
+        let climat = pallier.climat(entry.kind, &entry.climat)?;
+        (entry.ends_at > now).then_some((entry, climat))
+    });
+    if let Some((entry, climat)) = current {
+        return Ok(Some(Forecast {
+            entry,
+            climat,
+            changed: false,
+        }));
+    }
+
+    let (kind, climat) = pallier.draw(rng, settings.intemperie_chance);
+    // A climat lasting 0 weeks would be drawn again on every check.
+    let weeks = climat.duree.max(1) as i64;
+    let entry = WeatherEntry {
+        pallier: number as u32,
+        kind,
+        climat: climat.name.clone(),
+        started_at: now,
+        ends_at: now + weeks * settings.week_hours as i64 * 3600,
+    };
+    calendar.record(&entry)?;
+
+    Ok(Some(Forecast {
+        entry,
+        climat,
+        changed: true,
+    }))
+}